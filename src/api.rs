@@ -1,190 +1,439 @@
 use crate::{
-    service::{GeminiService, NotionService},
+    error::{AppError, AppResult},
+    retry::send_with_retry,
+    service::NotionService,
     types::*,
 };
-use reqwest::header::AUTHORIZATION;
+use reqwest::{header::AUTHORIZATION, RequestBuilder, Response};
+use serde::de::DeserializeOwned;
+
+// Notion のリスト系エンドポイントで 1 リクエストあたりに取得できる最大件数
+const NOTION_PAGE_SIZE: u32 = 100;
 
 pub async fn fetch_notion_page(
     service: &NotionService,
     page_id: &str,
-) -> Result<NotionPageDetail, Box<dyn std::error::Error>> {
-    let url = format!("https://api.notion.com/v1/blocks/{}/children", page_id);
-
-    let response = service
-        .client
-        .get(&url)
-        .header("Notion-Version", "2025-09-03")
-        .header(AUTHORIZATION, format!("Bearer {}", service.api_key))
-        .send()
+) -> AppResult<NotionPageDetail> {
+    let results = fetch_block_tree(service, page_id, service.max_block_depth)
         .await?
-        .json::<NotionBlockResponse>()
-        .await?;
+        .into_iter()
+        .map(parse_fetched_block)
+        .collect();
 
-    let page_detail = NotionPageDetail { body: response };
+    let page_detail = NotionPageDetail {
+        body: NotionBlockResponse {
+            results,
+            has_more: false,
+            next_cursor: None,
+        },
+    };
 
     Ok(page_detail)
 }
 
+// 1 つのブロックが読めないだけでページ全体の取得を失敗させないよう、未対応として扱う
+fn parse_fetched_block(block: serde_json::Value) -> NotionBlock {
+    let id = block["id"].as_str().unwrap_or_default().to_string();
+    serde_json::from_value(block).unwrap_or_else(|e| {
+        println!("Skipped block {} that could not be parsed: {}", id, e);
+        NotionBlock::Unsupported
+    })
+}
+
+// has_children を辿り、子ブロックを各ブロックの children に埋め込んだ JSON を返す
+// depth が 0 になったらそれ以上は潜らない
+async fn fetch_block_tree(
+    service: &NotionService,
+    block_id: &str,
+    depth: usize,
+) -> AppResult<Vec<serde_json::Value>> {
+    let mut blocks =
+        fetch_all_block_children::<NotionBlockValueListResponse>(service, block_id, "2025-09-03")
+            .await?;
+
+    if depth == 0 {
+        return Ok(blocks);
+    }
+
+    for block in blocks.iter_mut() {
+        let has_children = block["has_children"].as_bool().unwrap_or(false);
+        let block_type = block["type"].as_str().unwrap_or_default().to_string();
+        // 子ページ・子データベースの中身は別ページなので辿らない
+        if !has_children || block_type == "child_page" || block_type == "child_database" {
+            continue;
+        }
+        let Some(child_id) = block["id"].as_str().map(str::to_string) else {
+            continue;
+        };
+
+        let children = Box::pin(fetch_block_tree(service, &child_id, depth - 1)).await?;
+        if let Some(content) = block
+            .get_mut(&block_type)
+            .and_then(|content| content.as_object_mut())
+        {
+            content.insert("children".to_string(), serde_json::Value::Array(children));
+        }
+    }
+
+    Ok(blocks)
+}
+
+// next_cursor を辿って全ての子ブロックを取得する
+async fn fetch_all_block_children<R>(
+    service: &NotionService,
+    block_id: &str,
+    notion_version: &str,
+) -> AppResult<Vec<R::Item>>
+where
+    R: NotionPaginated + DeserializeOwned,
+{
+    let url = format!("https://api.notion.com/v1/blocks/{}/children", block_id);
+    let mut results = vec![];
+    let mut start_cursor: Option<String> = None;
+
+    loop {
+        let mut request = service
+            .client
+            .get(&url)
+            .query(&[("page_size", NOTION_PAGE_SIZE.to_string())]);
+        if let Some(cursor) = &start_cursor {
+            request = request.query(&[("start_cursor", cursor)]);
+        }
+
+        let request = request
+            .header("Notion-Version", notion_version)
+            .header(AUTHORIZATION, format!("Bearer {}", service.api_key));
+        let response = send_notion_request(service, request).await?;
+        let response = parse_notion_response::<R>(response).await?;
+
+        start_cursor = response.next_cursor().map(str::to_string);
+        results.extend(response.into_results());
+
+        if start_cursor.is_none() {
+            break;
+        }
+    }
+
+    Ok(results)
+}
+
+// Notion の制限に収まるよう分割して追加し、追加したトップレベルのブロック ID を返す
 pub async fn append_notion_block_to_page(
     service: &NotionService,
     page_id: &str,
     block_contents: Vec<NotionBlock>,
-) -> Result<(), Box<dyn std::error::Error>> {
-    let url = format!("https://api.notion.com/v1/blocks/{}/children", page_id);
-    let request_data = NotionAppendBlockRequest {
-        children: block_contents,
-        position: AppendPositionType::End,
-    };
+) -> AppResult<Vec<String>> {
+    append_notion_block_at(service, page_id, block_contents, AppendPositionType::End).await
+}
+
+// 位置を指定して追加する。分割した場合は前の塊の直後に続けて追加する
+pub async fn append_notion_block_at(
+    service: &NotionService,
+    page_id: &str,
+    block_contents: Vec<NotionBlock>,
+    position: AppendPositionType,
+) -> AppResult<Vec<String>> {
+    let blocks = block_contents
+        .into_iter()
+        .map(NotionBlock::split_long_rich_text)
+        .collect();
+    append_block_children(service, page_id, blocks, position).await
+}
+
+// 100 件ずつ追加し、深すぎて外した子ブロックは追加されたブロックの下に改めて追加する
+async fn append_block_children(
+    service: &NotionService,
+    parent_id: &str,
+    blocks: Vec<NotionBlock>,
+    mut position: AppendPositionType,
+) -> AppResult<Vec<String>> {
+    let mut appended_ids = vec![];
+
+    for chunk in chunk_blocks(blocks) {
+        let (chunk, detached): (Vec<_>, Vec<_>) = chunk
+            .into_iter()
+            .map(NotionBlock::detach_oversized_children)
+            .unzip();
+
+        let ids = append_block_batch(service, parent_id, chunk, position).await?;
+        for (id, children) in ids.iter().zip(detached) {
+            if let Some(children) = children {
+                Box::pin(append_detached_children(service, id, children)).await?;
+            }
+        }
+        position = match ids.last() {
+            Some(id) => AppendPositionType::AfterBlock {
+                after_block: NotionBlockReference { id: id.clone() },
+            },
+            None => AppendPositionType::End,
+        };
+        appended_ids.extend(ids);
+    }
+
+    Ok(appended_ids)
+}
+
+// 取り外しておいた子ブロックを、追加済みのブロック parent_id の下に追加する
+async fn append_detached_children(
+    service: &NotionService,
+    parent_id: &str,
+    detached: DetachedChildren,
+) -> AppResult<()> {
+    match detached {
+        DetachedChildren::Children(children) => {
+            append_block_children(service, parent_id, children, AppendPositionType::End).await?;
+        }
+        DetachedChildren::Columns(columns) => {
+            // 追加時のレスポンスには列の ID が含まれないため取得する
+            let column_ids = fetch_block_ids(service, parent_id).await?;
+            for (column, children) in column_ids.iter().zip(columns) {
+                if !children.is_empty() {
+                    append_block_children(service, &column.id, children, AppendPositionType::End)
+                        .await?;
+                }
+            }
+        }
+    }
+    Ok(())
+}
 
-    let response = service
+async fn append_block_batch(
+    service: &NotionService,
+    parent_id: &str,
+    children: Vec<NotionBlock>,
+    position: AppendPositionType,
+) -> AppResult<Vec<String>> {
+    let url = format!("https://api.notion.com/v1/blocks/{}/children", parent_id);
+    let request_data = NotionAppendBlockRequest { children, position };
+
+    let request = service
         .client
         .patch(&url)
         .header("Notion-Version", "2025-09-03")
         .header(AUTHORIZATION, format!("Bearer {}", service.api_key))
-        .json(&request_data)
-        .send()
-        .await?;
+        .json(&request_data);
+    let response = send_notion_write(service, request).await?;
 
-    let response_body = response.text().await;
+    let response_data = parse_notion_response::<NotionBlockIdListResponse>(response).await?;
+    println!("Notion page Append Result: {:?}", response_data);
 
-    println!("Notion page Append Result: {:?}", response_body);
-
-    Ok(())
+    Ok(response_data
+        .into_results()
+        .into_iter()
+        .map(|block| block.id)
+        .collect())
 }
 
 pub async fn query_database(
     service: &NotionService,
     database_id: &str,
     query: NotionDatabaseQuery,
-) -> Result<NotionDatabaseQueryResponse, Box<dyn std::error::Error>> {
+) -> AppResult<Vec<NotionPage>> {
     let url = format!("https://api.notion.com/v1/databases/{}/query", database_id);
     println!("Query Database URL: {:?}", url);
-    let response = service
-        .client
-        .post(&url)
-        .header("Notion-Version", "2022-06-28")
-        .header(AUTHORIZATION, format!("Bearer {}", service.api_key))
-        .json(&query)
-        .send()
-        .await?;
 
-    let status = response.status();
-    let body_text = response.text().await?;
+    let mut query = NotionDatabaseQuery {
+        page_size: query.page_size.or(Some(NOTION_PAGE_SIZE)),
+        ..query
+    };
+    let mut results = vec![];
 
-    if !status.is_success() {
-        println!("Query Database Error Status: {}", status);
-        println!("Query Database Error Body: {}", body_text);
-        return Err(format!("Notion API Error: Status {}, Body: {}", status, body_text).into());
+    loop {
+        let request = service
+            .client
+            .post(&url)
+            .header("Notion-Version", "2022-06-28")
+            .header(AUTHORIZATION, format!("Bearer {}", service.api_key))
+            .json(&query);
+        let response = send_notion_request(service, request).await?;
+
+        let response_data =
+            parse_notion_response::<NotionDatabaseQueryResponse>(response).await?;
+        query.start_cursor = response_data.next_cursor().map(str::to_string);
+        results.extend(response_data.into_results());
+
+        if query.start_cursor.is_none() {
+            break;
+        }
     }
 
-    let response_data: NotionDatabaseQueryResponse = serde_json::from_str(&body_text)?;
-    Ok(response_data)
+    Ok(results)
 }
 
-pub async fn create_page(
+pub async fn retrieve_database(
     service: &NotionService,
-    request: NotionCreatePageRequest,
-) -> Result<NotionPage, Box<dyn std::error::Error>> {
-    let url = "https://api.notion.com/v1/pages";
-    let response = service
+    database_id: &str,
+) -> AppResult<NotionDatabase> {
+    let url = format!("https://api.notion.com/v1/databases/{}", database_id);
+    let request = service
         .client
-        .post(url)
+        .get(url)
         .header("Notion-Version", "2022-06-28")
-        .header(AUTHORIZATION, format!("Bearer {}", service.api_key))
-        .json(&request)
-        .send()
-        .await?;
+        .header(AUTHORIZATION, format!("Bearer {}", service.api_key));
+    let response = send_notion_request(service, request).await?;
 
-    let status = response.status();
-    let body_text = response.text().await?;
+    parse_notion_response(response).await
+}
 
-    if !status.is_success() {
-        println!("Create Page Error Status: {}", status);
-        println!("Create Page Error Body: {}", body_text);
-        return Err(format!("Notion API Error: Status {}, Body: {}", status, body_text).into());
-    }
+pub async fn retrieve_page(service: &NotionService, page_id: &str) -> AppResult<NotionPage> {
+    let url = format!("https://api.notion.com/v1/pages/{}", page_id);
+    let request = service
+        .client
+        .get(url)
+        .header("Notion-Version", "2022-06-28")
+        .header(AUTHORIZATION, format!("Bearer {}", service.api_key));
+    let response = send_notion_request(service, request).await?;
 
-    let response_data: NotionPage = serde_json::from_str(&body_text)?;
-    Ok(response_data)
+    parse_notion_response(response).await
 }
 
-async fn push_to_gemini_api(
-    service: &GeminiService,
-    prompt: GeminiAPIPrompt,
-    model: GeminiAPIModel,
-) -> Result<GeminiAPIResponse, Box<dyn std::error::Error>> {
-    let model = model.model_name();
+pub async fn update_page_properties(
+    service: &NotionService,
+    page_id: &str,
+    properties: serde_json::Value,
+) -> AppResult<NotionPage> {
+    let url = format!("https://api.notion.com/v1/pages/{}", page_id);
+    let request = service
+        .client
+        .patch(url)
+        .header("Notion-Version", "2022-06-28")
+        .header(AUTHORIZATION, format!("Bearer {}", service.api_key))
+        .json(&serde_json::json!({ "properties": properties }));
+    let response = send_notion_request(service, request).await?;
 
-    let url = format!(
-        "https://generativelanguage.googleapis.com/v1beta/models/{}:generateContent?key={}",
-        model, service.api_key
-    );
+    parse_notion_response(response).await
+}
+
+// 本文は 1 回で送れる分だけ作成時に渡し、残りは作成したページに追加する
+pub async fn create_page(
+    service: &NotionService,
+    request: NotionCreatePageRequest,
+) -> AppResult<NotionPage> {
+    let mut children = vec![];
+    let mut detached = vec![];
+    let mut remaining = vec![];
+    for (index, block) in request
+        .children
+        .into_iter()
+        .map(NotionBlock::split_long_rich_text)
+        .enumerate()
+    {
+        if index >= MAX_CHILDREN_PER_REQUEST {
+            remaining.push(block);
+            continue;
+        }
+        let (block, block_children) = block.detach_oversized_children();
+        if let Some(block_children) = block_children {
+            detached.push((index, block_children));
+        }
+        children.push(block);
+    }
+    let request = NotionCreatePageRequest {
+        children,
+        ..request
+    };
 
-    let response = service
+    let url = "https://api.notion.com/v1/pages";
+    let http_request = service
         .client
         .post(url)
-        .json(&prompt)
-        .send()
-        .await?
-        .json::<GeminiAPIResponse>()
-        .await?;
+        .header("Notion-Version", "2022-06-28")
+        .header(AUTHORIZATION, format!("Bearer {}", service.api_key))
+        .json(&request);
+    let response = send_notion_write(service, http_request).await?;
+    let page: NotionPage = parse_notion_response(response).await?;
 
-    Ok(response)
-}
+    if !detached.is_empty() {
+        // 作成時のレスポンスには本文のブロック ID が含まれないため取得し直す
+        let created_ids = fetch_block_ids(service, &page.id).await?;
+        for (index, blocks) in detached {
+            if let Some(parent) = created_ids.get(index) {
+                append_detached_children(service, &parent.id, blocks).await?;
+            }
+        }
+    }
+    if !remaining.is_empty() {
+        append_block_children(service, &page.id, remaining, AppendPositionType::End).await?;
+    }
 
-pub async fn gen_notion_page_contents_from_gemini_api(
-    service: &GeminiService,
-    prompt: GeminiAPIPrompt,
-    model: GeminiAPIModel,
-) -> Result<Vec<NotionBlock>, Box<dyn std::error::Error>> {
-    let response_data = push_to_gemini_api(service, prompt, model).await?;
-    let generated_content_str = &response_data.candidates[0].content.parts[0].text;
-    println!("Generated Content String: {:?}", generated_content_str);
+    Ok(page)
+}
 
-    let generated_blocks: Vec<NotionBlock> = match serde_json::from_str(&generated_content_str) {
-        Ok(valid_blocks) => valid_blocks,
-        Err(_) => vec![NotionBlock::heading_3("AIレスポンス生成に失敗しました")],
-    };
-    println!("Generated Block List: {:?}", generated_blocks);
+// 子ブロックを深さの制限なく辿って取得する。削除前の退避に使う
+pub async fn fetch_full_block_tree(
+    service: &NotionService,
+    block_id: &str,
+) -> AppResult<Vec<serde_json::Value>> {
+    fetch_block_tree(service, block_id, usize::MAX).await
+}
 
-    Ok(generated_blocks)
+// ページ直下のブロックを中身ごと取得する (子ブロックは辿らない)
+pub async fn fetch_top_level_blocks(
+    service: &NotionService,
+    page_id: &str,
+) -> AppResult<Vec<serde_json::Value>> {
+    fetch_all_block_children::<NotionBlockValueListResponse>(service, page_id, "2025-09-03").await
 }
 
 pub async fn fetch_block_ids(
     service: &NotionService,
     page_id: &str,
-) -> Result<Vec<NotionBlockId>, Box<dyn std::error::Error>> {
-    let url = format!("https://api.notion.com/v1/blocks/{}/children", page_id);
-
-    let response = service
-        .client
-        .get(&url)
-        .header("Notion-Version", "2022-06-28")
-        .header(AUTHORIZATION, format!("Bearer {}", service.api_key))
-        .send()
-        .await?
-        .json::<NotionBlockIdListResponse>()
-        .await?;
-
-    Ok(response.results)
+) -> AppResult<Vec<NotionBlockId>> {
+    fetch_all_block_children::<NotionBlockIdListResponse>(service, page_id, "2022-06-28").await
 }
 
 pub async fn delete_block(
     service: &NotionService,
     block_id: &str,
-) -> Result<(), Box<dyn std::error::Error>> {
+) -> AppResult<()> {
     let url = format!("https://api.notion.com/v1/blocks/{}", block_id);
-    let response = service
+    let request = service
         .client
         .delete(&url)
         .header("Notion-Version", "2022-06-28")
-        .header(AUTHORIZATION, format!("Bearer {}", service.api_key))
-        .send()
-        .await?;
+        .header(AUTHORIZATION, format!("Bearer {}", service.api_key));
+    let response = send_notion_request(service, request).await?;
 
-    if !response.status().is_success() {
-        println!("Delete Block Error: {}", response.status());
+    let status = response.status();
+    if !status.is_success() {
+        let body_text = response.text().await?;
+        return Err(AppError::notion_api(status, &body_text));
     }
 
     Ok(())
-}
\ No newline at end of file
+}
+
+// Notion へのリクエストはすべてレートリミッタとリトライを通す
+async fn send_notion_request(
+    service: &NotionService,
+    request: RequestBuilder,
+) -> AppResult<Response> {
+    send_with_retry(&service.retry_policy, Some(&service.rate_limiter), request).await
+}
+
+// 二重に送ると重複するページ作成やブロック追加は、処理されていないと分かる失敗だけを再送する
+async fn send_notion_write(
+    service: &NotionService,
+    request: RequestBuilder,
+) -> AppResult<Response> {
+    send_with_retry(
+        &service.retry_policy.clone().non_idempotent(),
+        Some(&service.rate_limiter),
+        request,
+    )
+    .await
+}
+
+// ステータスを確認し、失敗時は Notion のエラーボディを AppError に変換する
+async fn parse_notion_response<T: DeserializeOwned>(response: Response) -> AppResult<T> {
+    let status = response.status();
+    let body_text = response.text().await?;
+
+    if !status.is_success() {
+        println!("Notion API Error Status: {}", status);
+        println!("Notion API Error Body: {}", body_text);
+        return Err(AppError::notion_api(status, &body_text));
+    }
+
+    Ok(serde_json::from_str(&body_text)?)
+}
@@ -1,3 +1,56 @@
+mod ai_section;
 pub mod diary;
+pub mod report;
 pub mod review;
-pub mod weekly_report;
\ No newline at end of file
+
+use axum::Json;
+use reqwest::StatusCode;
+use serde::Serialize;
+use serde_json::{json, Value};
+
+use crate::{
+    jobs::{EnqueuedJob, JobKind},
+    router::AppState,
+    types::NotionWebhookPayload,
+};
+
+/// ジョブ履歴に残す、自動化の実行結果
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct AutomationOutcome {
+    pub model: String,
+    pub appended_block_ids: Vec<String>,
+    // 本文を取得できなかったページ (レポートの材料にした日記など)
+    pub failed_page_ids: Vec<String>,
+}
+
+// webhook ではジョブをキューに積むだけにして、処理はワーカーに任せる
+fn accept_job(
+    state: &AppState,
+    kind: JobKind,
+    payload: &NotionWebhookPayload,
+) -> (StatusCode, Json<Value>) {
+    match state.job_queue.enqueue_once(kind, payload) {
+        Ok(EnqueuedJob { job, duplicate }) => {
+            if duplicate {
+                println!(
+                    "Skipped duplicate {} delivery (job {})",
+                    kind.as_str(),
+                    job.id
+                );
+            } else {
+                println!("Enqueued {} job {}", kind.as_str(), job.id);
+            }
+            (
+                StatusCode::OK,
+                Json(json!({ "job_id": job.id, "state": job.state, "duplicate": duplicate })),
+            )
+        }
+        Err(e) => {
+            println!("Failed to enqueue {} job: {}", kind.as_str(), e);
+            (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(json!({ "error": e.to_string() })),
+            )
+        }
+    }
+}
@@ -0,0 +1,112 @@
+use serde_json::Value;
+
+use crate::{
+    api::{append_notion_block_at, delete_block, fetch_full_block_tree, fetch_top_level_blocks},
+    error::AppResult,
+    service::NotionService,
+    snapshot::SnapshotStore,
+    types::{AppendPositionType, ExtractText, NotionBlock, NotionBlockReference},
+};
+
+// AI が生成した内容を囲むコールアウトの見出し。再実行時はこれを目印に置き換える
+pub const AI_SECTION_HEADER: &str = "🤖 AIによる生成内容 (再実行すると置き換わります)";
+
+/// 生成したブロックを目印付きのコールアウトで囲む
+pub fn ai_section(blocks: Vec<NotionBlock>) -> NotionBlock {
+    NotionBlock::callout(AI_SECTION_HEADER, blocks)
+}
+
+/// 前回の AI セクションがあればその位置に新しいセクションを置き、古いものを退避してから消す。
+/// 無ければページ末尾に追加する。ユーザーが書いた部分には触れない
+pub async fn replace_ai_section(
+    service: &NotionService,
+    snapshots: &SnapshotStore,
+    page_id: &str,
+    blocks: Vec<NotionBlock>,
+) -> AppResult<Vec<String>> {
+    let previous = find_ai_sections(service, page_id).await?;
+    let previous_ids: Vec<String> = previous
+        .iter()
+        .filter_map(|block| block["id"].as_str().map(str::to_string))
+        .collect();
+    if !previous.is_empty() {
+        let snapshot = snapshots.save(page_id, &previous)?;
+        println!(
+            "Saved snapshot {} of {} AI sections in page {}",
+            snapshot.id,
+            previous.len(),
+            page_id
+        );
+    }
+
+    let position = match previous_ids.first() {
+        Some(id) => AppendPositionType::AfterBlock {
+            after_block: NotionBlockReference { id: id.clone() },
+        },
+        None => AppendPositionType::End,
+    };
+
+    // 先に追加してから消すことで、途中で失敗してもフィードバックが消えたままにならない
+    let appended_block_ids =
+        append_notion_block_at(service, page_id, vec![ai_section(blocks)], position).await?;
+    for block_id in &previous_ids {
+        delete_block(service, block_id).await?;
+    }
+
+    Ok(appended_block_ids)
+}
+
+// 退避できるよう、見つけた AI セクションには子ブロックを埋め込んで返す
+async fn find_ai_sections(service: &NotionService, page_id: &str) -> AppResult<Vec<Value>> {
+    let mut sections = vec![];
+    for mut block in fetch_top_level_blocks(service, page_id).await? {
+        if !is_ai_section(&block) {
+            continue;
+        }
+        if let Some(id) = block["id"].as_str().map(str::to_string) {
+            let children = fetch_full_block_tree(service, &id).await?;
+            if let Some(callout) = block["callout"].as_object_mut() {
+                callout.insert("children".to_string(), Value::Array(children));
+            }
+            sections.push(block);
+        }
+    }
+    Ok(sections)
+}
+
+fn is_ai_section(block: &Value) -> bool {
+    match serde_json::from_value::<NotionBlock>(block.clone()) {
+        Ok(NotionBlock::Callout { callout }) => callout
+            .extract_text()
+            .is_some_and(|text| text.starts_with(AI_SECTION_HEADER)),
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_ai_section_is_recognized() {
+        let section =
+            serde_json::to_value(ai_section(vec![NotionBlock::paragraph("感想")])).unwrap();
+        assert_eq!(section["callout"]["children"][0]["type"], "paragraph");
+        assert!(is_ai_section(&section));
+
+        let user_callout = json!({
+            "id": "b1",
+            "type": "callout",
+            "callout": {"rich_text": [{"type": "text", "text": {"content": "メモ"}, "plain_text": "メモ"}]}
+        });
+        assert!(!is_ai_section(&user_callout));
+
+        let paragraph = json!({
+            "id": "b2",
+            "type": "paragraph",
+            "paragraph": {"rich_text": [{"type": "text", "text": {"content": AI_SECTION_HEADER}}]}
+        });
+        assert!(!is_ai_section(&paragraph));
+    }
+}
@@ -0,0 +1,528 @@
+use axum::{
+    extract::{Path, State},
+    Json,
+};
+use futures::{stream, StreamExt};
+use reqwest::StatusCode;
+use serde_json::{json, Value};
+
+use crate::{
+    api::{create_page, fetch_notion_page, query_database, retrieve_page, update_page_properties},
+    automation::{
+        accept_job,
+        ai_section::{ai_section, replace_ai_section},
+        AutomationOutcome,
+    },
+    config::AutomationConfig,
+    error::{AppError, AppResult},
+    jobs::{normalize_page_id, JobKind},
+    llm::{generate_notion_blocks, summarize::reduce_to_budget, LlmMessage, LlmRequest},
+    period::{ReportPeriod, ReportPeriodKind, ReportPeriodRequest},
+    router::AppState,
+    schema::{property_text, DiaryDatabaseSchema, ReportDatabaseSchema},
+    types::{
+        blocks_to_markdown, NotionBlock, NotionCreatePageRequest, NotionDatabaseQuery, NotionPage,
+        NotionWebhookPayload, Parent,
+    },
+};
+
+pub async fn handle_weekly_report(
+    State(state): State<AppState>,
+    Json(payload): Json<NotionWebhookPayload>,
+) -> (StatusCode, Json<Value>) {
+    accept_job(&state, JobKind::WeeklyReport, &payload)
+}
+
+/// URL で指定した期間のレポートを作る。payload またはページの日付プロパティで start / end を渡すと
+/// 任意の期間になる
+pub async fn handle_periodic_report(
+    State(state): State<AppState>,
+    Path(kind): Path<ReportPeriodKind>,
+    Json(mut payload): Json<NotionWebhookPayload>,
+) -> (StatusCode, Json<Value>) {
+    let period_request = payload.period.get_or_insert_with(Default::default);
+    period_request.kind = Some(kind);
+    // 期間の指定が誤っている場合はリトライしても直らないので、キューに積まずに返す
+    let period = match ReportPeriod::resolve(
+        Some(period_request),
+        kind,
+        state.calendar.today(),
+        state.calendar.week_alignment,
+    ) {
+        Ok(period) => period,
+        Err(e) => {
+            return (
+                StatusCode::BAD_REQUEST,
+                Json(json!({ "error": e.to_string() })),
+            )
+        }
+    };
+    // 日付を指定した場合は解決した期間を積み、重複判定のキーにも含める。
+    // 指定がなければ、処理時にページの日付プロパティから決める
+    if period_request.start.is_some() {
+        period_request.start = Some(period.start);
+        period_request.end = Some(period.end);
+    }
+    accept_job(&state, JobKind::PeriodicReport, &payload)
+}
+
+pub async fn periodic_report_process(
+    state: &AppState,
+    payload: NotionWebhookPayload,
+    default_kind: ReportPeriodKind,
+) -> AppResult<AutomationOutcome> {
+    let report_page_id = &payload.data.id;
+
+    // 1. Resolve the report period in the configured timezone
+    // payload で日付を指定していなければ、webhook を送ったページの日付プロパティを使う
+    let mut period_request = payload.period.clone().unwrap_or_default();
+    if period_request.start.is_none() && period_request.end.is_none() {
+        if let Some(page_request) = page_period_request(state, report_page_id).await? {
+            period_request.start = page_request.start;
+            period_request.end = page_request.end;
+        }
+    }
+    let period = ReportPeriod::resolve(
+        Some(&period_request),
+        default_kind,
+        state.calendar.today(),
+        state.calendar.week_alignment,
+    )?;
+
+    println!(
+        "Generating {} report for period: {} to {}",
+        period.kind.as_str(),
+        period.start,
+        period.end
+    );
+
+    // 2. Collect the source text (finer reports for longer periods, diaries otherwise)
+    let source = collect_source_text(state, &period).await?;
+    if !source.failed_page_ids.is_empty() {
+        println!(
+            "Skipped {} pages that could not be fetched",
+            source.failed_page_ids.len()
+        );
+    }
+
+    // 取得に失敗したページしかない場合は「見つからなかった」と書かず、ジョブを再試行させる
+    if source.text.is_empty() && !source.failed_page_ids.is_empty() {
+        return Err(AppError::SourcePagesUnavailable {
+            count: source.failed_page_ids.len(),
+        });
+    }
+
+    if source.text.is_empty() {
+        println!("No diary content found for the period.");
+        // 前回のレポートは残さず、見つからなかったことに置き換える
+        let appended_block_ids = replace_ai_section(
+            &state.notion_service,
+            &state.snapshots,
+            report_page_id,
+            vec![NotionBlock::paragraph(
+                "対象期間の日記が見つかりませんでした。",
+            )],
+        )
+        .await?;
+        return Ok(AutomationOutcome {
+            model: String::new(),
+            appended_block_ids,
+            failed_page_ids: source.failed_page_ids,
+        });
+    }
+
+    // 3. Generate Prompt (summarize chunks first when the source exceeds the budget)
+    let automation = state.automations.for_period(period.kind);
+    let provider = state.llm_providers.get(automation.provider);
+    let source_text = reduce_to_budget(provider.as_ref(), automation, source.text).await?;
+    let request = gen_report_prompt(&period, source_text, automation);
+
+    // 4. Call LLM
+    let gened_blocks = generate_notion_blocks(provider.as_ref(), &request).await?;
+
+    // 5. Replace the AI Section in Report Page (Webhook Source)
+    println!("Replacing AI section in report page: {}", report_page_id);
+    // Clone blocks for reuse
+    let appended_block_ids = replace_ai_section(
+        &state.notion_service,
+        &state.snapshots,
+        report_page_id,
+        gened_blocks.clone(),
+    )
+    .await?;
+
+    // 6. Upsert the page for the period in Report DB
+    upsert_report_page(state, &period, report_page_id, gened_blocks).await?;
+
+    Ok(AutomationOutcome {
+        model: request.params.model,
+        appended_block_ids,
+        failed_page_ids: source.failed_page_ids,
+    })
+}
+
+// 同じ期間のページがあればプロパティと AI セクションを更新し、無ければ作る
+async fn upsert_report_page(
+    state: &AppState,
+    period: &ReportPeriod,
+    report_page_id: &str,
+    blocks: Vec<NotionBlock>,
+) -> AppResult<()> {
+    let properties = report_properties(&state.notion_service.report_schema, period);
+    let Some(page) = find_report_page(state, period).await? else {
+        let create_page_request = NotionCreatePageRequest {
+            parent: Parent {
+                database_id: state
+                    .notion_service
+                    .report_db_id_for(period.kind)
+                    .to_string(),
+            },
+            properties,
+            children: vec![ai_section(blocks)],
+        };
+        let page = create_page(&state.notion_service, create_page_request).await?;
+        println!("Created new report page: {}", page.url);
+        return Ok(());
+    };
+
+    update_page_properties(&state.notion_service, &page.id, properties).await?;
+    // webhook を送ったページ自身がレポート DB にある場合、本文は置き換え済み
+    if normalize_page_id(&page.id) != normalize_page_id(report_page_id) {
+        replace_ai_section(&state.notion_service, &state.snapshots, &page.id, blocks).await?;
+    }
+    println!("Updated existing report page: {}", page.url);
+    Ok(())
+}
+
+// 開始日とタイトルの接頭辞で絞り込み、終了日まで一致するページを探す
+async fn find_report_page(
+    state: &AppState,
+    period: &ReportPeriod,
+) -> AppResult<Option<NotionPage>> {
+    let report_schema = &state.notion_service.report_schema;
+    let query = NotionDatabaseQuery {
+        filter: Some(json!({
+            "and": [
+                {
+                    "property": report_schema.date.name,
+                    "date": {
+                        "equals": period.start.format("%Y-%m-%d").to_string()
+                    }
+                },
+                {
+                    "property": report_schema.title.name,
+                    "title": {
+                        "starts_with": period.kind.title_prefix()
+                    }
+                }
+            ]
+        })),
+        ..Default::default()
+    };
+    let pages = query_database(
+        &state.notion_service,
+        state.notion_service.report_db_id_for(period.kind),
+        query,
+    )
+    .await?;
+    Ok(pages
+        .into_iter()
+        .find(|page| covers_period(report_schema, page, period)))
+}
+
+fn covers_period(schema: &ReportDatabaseSchema, page: &NotionPage, period: &ReportPeriod) -> bool {
+    let date = &page.properties[schema.date.name.as_str()]["date"];
+    let day = |key: &str| date[key].as_str().and_then(|value| value.get(..10));
+    let start = period.start.format("%Y-%m-%d").to_string();
+    let end = period.end.format("%Y-%m-%d").to_string();
+    day("start") == Some(start.as_str()) && day("end").or(day("start")) == Some(end.as_str())
+}
+
+fn report_properties(schema: &ReportDatabaseSchema, period: &ReportPeriod) -> Value {
+    json!({
+        schema.title.name.as_str(): {
+            "title": [
+                {
+                    "text": {
+                        "content": period.title()
+                    }
+                }
+            ]
+        },
+        // 期間は設定したタイムゾーンで計算済みなので、時刻を付けずに日付だけを書き込む
+        schema.date.name.as_str(): {
+            "date": {
+                "start": period.start.format("%Y-%m-%d").to_string(),
+                "end": period.end.format("%Y-%m-%d").to_string()
+            }
+        }
+    })
+}
+
+// 設定した日付プロパティがページに無い、または空の場合は None
+async fn page_period_request(
+    state: &AppState,
+    page_id: &str,
+) -> AppResult<Option<ReportPeriodRequest>> {
+    let Some(property) = &state.notion_service.report_period_property else {
+        return Ok(None);
+    };
+    let page = retrieve_page(&state.notion_service, page_id).await?;
+    let request = page
+        .properties
+        .get(property)
+        .and_then(|value| value.get("date"))
+        .and_then(|date| ReportPeriodRequest::from_date_property(date, state.calendar.timezone));
+    if request.is_none() {
+        println!(
+            "Page {} has no {} date, using the default period",
+            page_id, property
+        );
+    }
+    Ok(request)
+}
+
+// 日記やレポートの本文をまとめた、プロンプトに渡すテキスト
+#[derive(Debug, Default)]
+struct SourceText {
+    text: String,
+    // 本文を取得できず、テキストに含められなかったページ
+    failed_page_ids: Vec<String>,
+}
+
+// 長い期間は一段細かいレポートを要約する。見つからなければ日記から直接まとめる
+async fn collect_source_text(state: &AppState, period: &ReportPeriod) -> AppResult<SourceText> {
+    let mut failed_page_ids = vec![];
+    if let Some(finer) = period.kind.finer() {
+        let reports = collect_report_text(state, period, finer).await?;
+        if !reports.text.is_empty() {
+            return Ok(reports);
+        }
+        println!(
+            "No {} reports found for the period, falling back to diaries",
+            finer.as_str()
+        );
+        failed_page_ids = reports.failed_page_ids;
+    }
+    let mut diaries = collect_diary_text(state, period).await?;
+    failed_page_ids.append(&mut diaries.failed_page_ids);
+    diaries.failed_page_ids = failed_page_ids;
+    Ok(diaries)
+}
+
+async fn collect_diary_text(state: &AppState, period: &ReportPeriod) -> AppResult<SourceText> {
+    let diary_schema = &state.notion_service.diary_schema;
+    let query = NotionDatabaseQuery {
+        filter: Some(json!({
+            "and": date_range_filter(&diary_schema.date.name, period)
+        })),
+        sorts: Some(vec![json!({
+            "property": diary_schema.date.name,
+            "direction": "ascending"
+        })]),
+        ..Default::default()
+    };
+
+    let diary_entries = query_database(
+        &state.notion_service,
+        &state.notion_service.diary_db_id,
+        query,
+    )
+    .await?;
+
+    println!("Found {} diary entries", diary_entries.len());
+
+    Ok(fetch_source_text(state, &diary_entries, |page| {
+        format!("Diary Entry ({})", diary_entry_label(diary_schema, page))
+    })
+    .await)
+}
+
+// 期間内に作られた一段細かいレポートを、タイトルの接頭辞で探す
+async fn collect_report_text(
+    state: &AppState,
+    period: &ReportPeriod,
+    finer: ReportPeriodKind,
+) -> AppResult<SourceText> {
+    let report_schema = &state.notion_service.report_schema;
+    let mut conditions = date_range_filter(&report_schema.date.name, period);
+    conditions.push(json!({
+        "property": report_schema.title.name,
+        "title": {
+            "starts_with": finer.title_prefix()
+        }
+    }));
+    let query = NotionDatabaseQuery {
+        filter: Some(json!({ "and": conditions })),
+        sorts: Some(vec![json!({
+            "property": report_schema.date.name,
+            "direction": "ascending"
+        })]),
+        ..Default::default()
+    };
+
+    let reports = query_database(
+        &state.notion_service,
+        state.notion_service.report_db_id_for(finer),
+        query,
+    )
+    .await?;
+
+    println!("Found {} {} reports", reports.len(), finer.as_str());
+
+    Ok(fetch_source_text(state, &reports, |page| {
+        property_text(&page.properties, &report_schema.title.name)
+            .unwrap_or_else(|| page.id.clone())
+    })
+    .await)
+}
+
+// 本文を fetch_concurrency 件まで並行して取得し、pages の順に見出しを付けて連結する。
+// リクエストはすべてレートリミッタを通るので、並行数を上げても Notion の制限は超えない。
+// 取得に失敗したページは failed_page_ids に残し、空のページは飛ばす
+async fn fetch_source_text(
+    state: &AppState,
+    pages: &[NotionPage],
+    header: impl Fn(&NotionPage) -> String,
+) -> SourceText {
+    // クロージャを挟んだストリームでは Send の推論が通らないので、先に Future を作っておく
+    let fetches: Vec<_> = pages
+        .iter()
+        .map(|page| fetch_notion_page(&state.notion_service, &page.id))
+        .collect();
+    let results: Vec<_> = stream::iter(fetches)
+        .buffered(state.notion_service.fetch_concurrency)
+        .collect()
+        .await;
+
+    let mut source = SourceText::default();
+    for (page, result) in pages.iter().zip(results) {
+        match result {
+            Ok(page_detail) => {
+                let page_text = blocks_to_markdown(&page_detail.body.results);
+                if !page_text.trim().is_empty() {
+                    source
+                        .text
+                        .push_str(&format!("\n--- {} ---\n{}\n", header(page), page_text));
+                }
+            }
+            Err(e) => {
+                println!("Failed to fetch content for page {}: {}", page.id, e);
+                source.failed_page_ids.push(page.id.clone());
+            }
+        }
+    }
+    source
+}
+
+fn date_range_filter(property: &str, period: &ReportPeriod) -> Vec<Value> {
+    vec![
+        json!({
+            "property": property,
+            "date": {
+                "on_or_after": period.start.format("%Y-%m-%d").to_string()
+            }
+        }),
+        json!({
+            "property": property,
+            "date": {
+                "on_or_before": period.end.format("%Y-%m-%d").to_string()
+            }
+        }),
+    ]
+}
+
+// 日記の見出しに、日付やタグなど設定されたプロパティの値を添える
+fn diary_entry_label(schema: &DiaryDatabaseSchema, page: &NotionPage) -> String {
+    let mut label =
+        vec![property_text(&page.properties, &schema.date.name).unwrap_or_else(|| page.id.clone())];
+    if let Some(title) = property_text(&page.properties, &schema.title.name) {
+        label.push(title);
+    }
+    if let Some(tags) = schema
+        .tags
+        .as_ref()
+        .and_then(|tags| property_text(&page.properties, &tags.name))
+    {
+        label.push(format!("tags: {}", tags));
+    }
+    if let Some(mood) = schema
+        .mood
+        .as_ref()
+        .and_then(|mood| property_text(&page.properties, &mood.name))
+    {
+        label.push(format!("mood: {}", mood));
+    }
+    label.join(", ")
+}
+
+fn gen_report_prompt(
+    period: &ReportPeriod,
+    source_text: String,
+    automation: &AutomationConfig,
+) -> LlmRequest {
+    let content = format!(
+        "対象期間: {} ~ {}\n{}",
+        period.start, period.end, source_text
+    );
+    LlmRequest::new(automation, vec![LlmMessage::user(content)])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{config::test_config, period::WeekAlignment};
+    use chrono::NaiveDate;
+
+    #[test]
+    fn test_gen_report_prompt() {
+        let today = NaiveDate::from_ymd_opt(2024, 3, 1).unwrap();
+        let period =
+            ReportPeriod::ending_at(ReportPeriodKind::Month, today, WeekAlignment::Rolling);
+        let automations = test_config().automations;
+
+        let prompt = gen_report_prompt(
+            &period,
+            "\n--- 週報 2024-02-01 ~ 2024-02-08 ---\n忙しい週だった\n".to_string(),
+            automations.for_period(period.kind),
+        );
+
+        assert_eq!(prompt.messages.len(), 1);
+        assert!(prompt.messages[0]
+            .content
+            .starts_with("対象期間: 2024-02-01 ~ 2024-02-29\n"));
+        assert!(prompt.system_prompt.contains("月次レポート"));
+    }
+
+    #[test]
+    fn test_covers_period() {
+        let schema = ReportDatabaseSchema::default();
+        let period = ReportPeriod::ending_at(
+            ReportPeriodKind::Month,
+            NaiveDate::from_ymd_opt(2024, 3, 1).unwrap(),
+            WeekAlignment::Rolling,
+        );
+        let page = |date: Value| NotionPage {
+            id: "page".to_string(),
+            properties: json!({ "日付": { "type": "date", "date": date } }),
+            url: String::new(),
+        };
+
+        assert!(covers_period(
+            &schema,
+            &page(json!({"start": "2024-02-01", "end": "2024-02-29"})),
+            &period
+        ));
+        assert!(!covers_period(
+            &schema,
+            &page(json!({"start": "2024-02-01", "end": "2024-02-07"})),
+            &period
+        ));
+        assert!(!covers_period(
+            &schema,
+            &page(json!({"start": "2024-02-01", "end": null})),
+            &period
+        ));
+        assert!(!covers_period(&schema, &page(Value::Null), &period));
+    }
+}
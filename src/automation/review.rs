@@ -1,95 +1,70 @@
 use axum::{extract::State, Json};
 use reqwest::StatusCode;
+use serde_json::Value;
 
 use crate::{
-    api::{
-        append_notion_block_to_page, fetch_notion_page, gen_notion_page_contents_from_gemini_api,
-    },
+    api::fetch_notion_page,
+    automation::{accept_job, ai_section::replace_ai_section, AutomationOutcome},
+    config::AutomationConfig,
+    error::AppResult,
+    jobs::JobKind,
+    llm::{generate_notion_blocks, LlmMessage, LlmRequest},
     router::AppState,
-    types::{
-        ExtractText, GeminiAPIChatContent, GeminiAPIModel, GeminiAPIPrompt, GenerationConfig,
-        NotionPageDetail, NotionWebhookPayload, Part, Role,
-    },
+    types::{blocks_to_markdown, NotionPageDetail, NotionWebhookPayload},
 };
 
 pub async fn handle_review_automation(
     State(state): State<AppState>,
     Json(payload): Json<NotionWebhookPayload>,
-) -> StatusCode {
-    tokio::spawn(async move {
-        match review_automation_process(&state, payload).await {
-            Ok(_) => println!("Automation completed successfully"),
-            Err(e) => println!("Automation failed: {}", e),
-        }
-    });
-
-    StatusCode::OK
+) -> (StatusCode, Json<Value>) {
+    accept_job(&state, JobKind::Review, &payload)
 }
 
 pub async fn review_automation_process(
     state: &AppState,
     payload: NotionWebhookPayload,
-) -> Result<(), Box<dyn std::error::Error>> {
+) -> AppResult<AutomationOutcome> {
     println!("Webhook payload: {:?}", payload);
     let page_id = &payload.data.id;
     let notion_page_content = fetch_notion_page(&state.notion_service, page_id).await?;
     println!("Notion Page Content: {:?}", notion_page_content);
 
-    let prompt = gen_review_prompt(notion_page_content);
+    let automation = &state.automations.review;
+    let request = gen_review_prompt(notion_page_content, automation);
 
-    let gened_block_contents = gen_notion_page_contents_from_gemini_api(
-        &state.gemini_service,
-        prompt,
-        GeminiAPIModel::Gemini3Pro,
-    )
-    .await?;
+    let provider = state.llm_providers.get(automation.provider);
+    let gened_block_contents = generate_notion_blocks(provider.as_ref(), &request).await?;
 
-    println!("Gemini API Response: {gened_block_contents:?}");
+    println!("{} Response: {:?}", provider.name(), gened_block_contents);
 
-    append_notion_block_to_page(&state.notion_service, page_id, gened_block_contents).await?;
+    let appended_block_ids = replace_ai_section(
+        &state.notion_service,
+        &state.snapshots,
+        page_id,
+        gened_block_contents,
+    )
+    .await?;
 
-    Ok(())
+    Ok(AutomationOutcome {
+        model: request.params.model,
+        appended_block_ids,
+        ..Default::default()
+    })
 }
 
-fn gen_review_prompt(page_detail: NotionPageDetail) -> GeminiAPIPrompt {
-    let system_instruction_str = include_str!("../prompts/review_prompt.txt").to_string();
-    let mut system_instruction_parts = vec![];
-    system_instruction_parts.push(Part {
-        text: system_instruction_str,
-    });
+fn gen_review_prompt(page_detail: NotionPageDetail, automation: &AutomationConfig) -> LlmRequest {
+    let page_text = blocks_to_markdown(&page_detail.body.results);
 
-    let system_instruction = Some(GeminiAPIChatContent {
-        role: Some(Role::User),
-        parts: system_instruction_parts,
-    });
-
-    let page_contents: Vec<Part> = page_detail
-        .body
-        .results
-        .iter()
-        .filter_map(|b| b.extract_text())
-        .map(|text| Part { text })
-        .collect();
-
-    let mut contents = vec![];
-    contents.push(GeminiAPIChatContent {
-        role: Some(Role::User),
-        parts: page_contents,
-    });
-
-    let generation_config = Some(GenerationConfig::default());
-
-    GeminiAPIPrompt {
-        contents,
-        system_instruction,
-        generation_config,
-    }
+    LlmRequest::new(automation, vec![LlmMessage::user(page_text)])
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::types::{NotionBlockResponse, NotionBlock};
+    use crate::{
+        config::test_config,
+        types::{NotionBlock, NotionBlockResponse},
+    };
 
     #[test]
     fn test_gen_review_prompt() {
@@ -99,15 +74,19 @@ mod tests {
                     NotionBlock::paragraph("I learned about Rust tests."),
                     NotionBlock::code("fn test() {}", "rust".to_string()),
                 ],
+                has_more: false,
+                next_cursor: None,
             },
         };
 
-        let prompt = gen_review_prompt(page_detail);
+        let prompt = gen_review_prompt(page_detail, &test_config().automations.review);
 
-        assert_eq!(prompt.contents.len(), 1);
-        assert_eq!(prompt.contents[0].parts.len(), 2);
-        assert_eq!(prompt.contents[0].parts[0].text, "I learned about Rust tests.");
-        assert_eq!(prompt.contents[0].parts[1].text, "fn test() {}");
-        assert!(prompt.system_instruction.is_some());
+        assert_eq!(prompt.messages.len(), 1);
+        assert_eq!(
+            prompt.messages[0].content,
+            "I learned about Rust tests.\n\n```rust\nfn test() {}\n```"
+        );
+        assert!(!prompt.system_prompt.is_empty());
+        assert!(prompt.json_mode);
     }
 }
@@ -0,0 +1,970 @@
+use std::{collections::HashMap, env, fs, net::SocketAddr, path::PathBuf};
+
+use chrono::{NaiveDate, Utc};
+use chrono_tz::Tz;
+use croner::Cron;
+use serde::Deserialize;
+use toml::{Table, Value};
+
+use crate::{
+    error::{AppError, AppResult},
+    jobs::JobKind,
+    period::{ReportPeriodKind, WeekAlignment},
+};
+
+const DEFAULT_CONFIG: &str = include_str!("config/default.toml");
+const DEFAULT_CONFIG_PATH: &str = "config.toml";
+// 週報・月報・四半期報・年報で共有するプロンプト。{span} などの期間ごとの語句を埋めて使う
+const REPORT_PROMPT_TEMPLATE: &str = include_str!("prompts/report.txt");
+
+// 環境変数名 → 上書きする設定キー
+const ENV_OVERRIDES: &[(&str, &str, ValueKind)] = &[
+    ("BIND_ADDRESS", "server.bind_address", ValueKind::String),
+    (
+        "NOTION_WEBHOOK_VERIFICATION_TOKEN",
+        "server.webhook_verification_token",
+        ValueKind::String,
+    ),
+    ("ADMIN_TOKEN", "server.admin_token", ValueKind::String),
+    ("NOTION_API_KEY", "notion.api_key", ValueKind::String),
+    (
+        "NOTION_MAX_BLOCK_DEPTH",
+        "notion.max_block_depth",
+        ValueKind::Integer,
+    ),
+    (
+        "NOTION_FETCH_CONCURRENCY",
+        "notion.fetch_concurrency",
+        ValueKind::Integer,
+    ),
+    (
+        "NOTION_REQUESTS_PER_SECOND",
+        "notion.requests_per_second",
+        ValueKind::Float,
+    ),
+    (
+        "NOTION_TIMEOUT_SECONDS",
+        "notion.timeout_seconds",
+        ValueKind::Integer,
+    ),
+    ("GEMINI_API_KEY", "gemini.api_key", ValueKind::String),
+    ("OPENAI_BASE_URL", "openai.base_url", ValueKind::String),
+    ("OPENAI_API_KEY", "openai.api_key", ValueKind::String),
+    ("OLLAMA_BASE_URL", "ollama.base_url", ValueKind::String),
+    (
+        "GEMINI_TIMEOUT_SECONDS",
+        "gemini.timeout_seconds",
+        ValueKind::Integer,
+    ),
+    (
+        "HTTP_MAX_RETRY_ATTEMPTS",
+        "http.max_retry_attempts",
+        ValueKind::Integer,
+    ),
+    (
+        "STATE_DATABASE_PATH",
+        "store.database_path",
+        ValueKind::String,
+    ),
+    ("JOB_MAX_ATTEMPTS", "jobs.max_attempts", ValueKind::Integer),
+    (
+        "JOB_WORKER_CONCURRENCY",
+        "jobs.worker_concurrency",
+        ValueKind::Integer,
+    ),
+    (
+        "WEBHOOK_DEDUP_WINDOW_SECONDS",
+        "jobs.dedup_window_seconds",
+        ValueKind::Integer,
+    ),
+    ("TIMEZONE", "calendar.timezone", ValueKind::String),
+    (
+        "REPORT_WEEK_ALIGNMENT",
+        "calendar.week_alignment",
+        ValueKind::String,
+    ),
+    ("NOTION_DIARY_DB_ID", "diary.database_id", ValueKind::String),
+    (
+        "NOTION_DIARY_DATE_PROPERTY",
+        "diary.properties.date",
+        ValueKind::String,
+    ),
+    (
+        "NOTION_DIARY_TITLE_PROPERTY",
+        "diary.properties.title",
+        ValueKind::String,
+    ),
+    (
+        "NOTION_DIARY_TAGS_PROPERTY",
+        "diary.properties.tags",
+        ValueKind::String,
+    ),
+    (
+        "NOTION_DIARY_MOOD_PROPERTY",
+        "diary.properties.mood",
+        ValueKind::String,
+    ),
+    (
+        "NOTION_REPORT_DB_ID",
+        "report.database_id",
+        ValueKind::String,
+    ),
+    (
+        "NOTION_REPORT_TITLE_PROPERTY",
+        "report.properties.title",
+        ValueKind::String,
+    ),
+    (
+        "NOTION_REPORT_DATE_PROPERTY",
+        "report.properties.date",
+        ValueKind::String,
+    ),
+    (
+        "NOTION_REPORT_PERIOD_PROPERTY",
+        "report.period_property",
+        ValueKind::String,
+    ),
+];
+
+#[derive(Debug, Clone, Copy)]
+enum ValueKind {
+    String,
+    Integer,
+    Float,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Config {
+    pub server: ServerConfig,
+    pub notion: NotionConfig,
+    pub gemini: GeminiConfig,
+    pub openai: OpenAiConfig,
+    pub ollama: OllamaConfig,
+    pub http: HttpConfig,
+    pub store: StoreConfig,
+    pub jobs: JobsConfig,
+    pub calendar: CalendarConfig,
+    pub scheduler: SchedulerConfig,
+    pub diary: DiaryConfig,
+    pub report: ReportConfig,
+    pub automations: AutomationsConfig,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ServerConfig {
+    pub bind_address: String,
+    // サブスクリプション作成時のハンドシェイクで受け取る verification_token
+    pub webhook_verification_token: Option<String>,
+    // /jobs などの管理用 API に `Authorization: Bearer <token>` で渡すトークン
+    pub admin_token: Option<String>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct NotionConfig {
+    pub api_key: String,
+    pub max_block_depth: usize,
+    // レポートの材料にするページを同時に取得する数
+    pub fetch_concurrency: usize,
+    pub requests_per_second: f64,
+    pub timeout_seconds: u64,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct GeminiConfig {
+    // Gemini を使う自動化がある場合のみ必須
+    #[serde(default)]
+    pub api_key: String,
+    pub timeout_seconds: u64,
+}
+
+/// OpenAI 互換の Chat Completions API (OpenAI、vLLM、LM Studio など)
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct OpenAiConfig {
+    pub base_url: String,
+    // ローカルのサーバーでは不要なことが多い
+    pub api_key: Option<String>,
+    pub timeout_seconds: u64,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct OllamaConfig {
+    pub base_url: String,
+    pub timeout_seconds: u64,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct HttpConfig {
+    // 初回を含めた最大試行回数
+    pub max_retry_attempts: u32,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct StoreConfig {
+    pub database_path: PathBuf,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct JobsConfig {
+    pub max_attempts: u32,
+    pub worker_concurrency: usize,
+    pub dedup_window_seconds: i64,
+}
+
+/// 日付の計算に使う暦
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct CalendarConfig {
+    // レポートの期間や日付、cron 式を解釈するタイムゾーン (IANA 名)
+    pub timezone: Tz,
+    pub week_alignment: WeekAlignment,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct SchedulerConfig {
+    #[serde(default)]
+    pub schedules: Vec<ScheduleConfig>,
+}
+
+/// webhook を待たずに定期実行する自動化
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ScheduleConfig {
+    // 前回の実行時刻を記録するキー。変えると初回扱いになる
+    pub name: String,
+    // 5 フィールド (秒を含めるなら 6 フィールド) の cron 式
+    pub cron: Cron,
+    pub automation: JobKind,
+    // 自動化に渡すページ (週報なら結果を書き込むページ)
+    pub page_id: String,
+    // periodic_report の期間。省略時は week
+    pub period: Option<ReportPeriodKind>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct DiaryConfig {
+    pub database_id: String,
+    pub properties: DiaryPropertiesConfig,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct DiaryPropertiesConfig {
+    pub date: String,
+    pub title: String,
+    pub tags: Option<String>,
+    pub mood: Option<String>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ReportConfig {
+    pub database_id: String,
+    // 期間ごとに書き込み先を分ける場合のデータベース。無い期間は database_id を使う
+    #[serde(default)]
+    pub databases: HashMap<ReportPeriodKind, String>,
+    // webhook を送ったページにこの日付プロパティがあれば、その期間でレポートを作る
+    pub period_property: Option<String>,
+    pub properties: ReportPropertiesConfig,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ReportPropertiesConfig {
+    pub title: String,
+    pub date: String,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct AutomationsConfig {
+    pub diary: AutomationConfig,
+    pub review: AutomationConfig,
+    pub weekly_report: AutomationConfig,
+    pub monthly_report: AutomationConfig,
+    pub quarterly_report: AutomationConfig,
+    pub yearly_report: AutomationConfig,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct AutomationConfig {
+    pub provider: LlmProviderKind,
+    pub model: String,
+    pub temperature: f32,
+    // ブロックを JSON で生成させるか、Markdown で生成させて変換するか
+    pub output: OutputFormat,
+    // 省略時は組み込みのプロンプトを使う
+    pub prompt_path: Option<PathBuf>,
+    // 読み込み時に prompt_path (または組み込みのプロンプト) から設定される
+    #[serde(skip)]
+    pub system_prompt: String,
+    // 入力の上限。超えた場合は先にチャンクごとに要約してから生成する (レポートのみ)
+    pub budget: Option<InputBudgetConfig>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct InputBudgetConfig {
+    // どちらかを超えたら要約する。トークン数は文字の種類から大まかに見積もる
+    pub max_chars: Option<usize>,
+    pub max_tokens: Option<usize>,
+    // 1 回の要約に渡す文字数
+    pub chunk_chars: usize,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LlmProviderKind {
+    Gemini,
+    OpenAi,
+    Ollama,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum OutputFormat {
+    Json,
+    Markdown,
+}
+
+impl CalendarConfig {
+    /// 設定したタイムゾーンでの今日
+    pub fn today(&self) -> NaiveDate {
+        Utc::now().with_timezone(&self.timezone).date_naive()
+    }
+}
+
+impl AutomationsConfig {
+    pub fn iter(&self) -> impl Iterator<Item = &AutomationConfig> {
+        [
+            &self.diary,
+            &self.review,
+            &self.weekly_report,
+            &self.monthly_report,
+            &self.quarterly_report,
+            &self.yearly_report,
+        ]
+        .into_iter()
+    }
+
+    pub fn for_period(&self, kind: ReportPeriodKind) -> &AutomationConfig {
+        match kind {
+            ReportPeriodKind::Week => &self.weekly_report,
+            ReportPeriodKind::Month => &self.monthly_report,
+            ReportPeriodKind::Quarter => &self.quarterly_report,
+            ReportPeriodKind::Year => &self.yearly_report,
+        }
+    }
+}
+
+impl Config {
+    /// 組み込みの既定値、CONFIG_PATH (既定: config.toml)、環境変数の順に重ねて読み込む
+    pub fn load() -> AppResult<Self> {
+        let (path, required) = match env::var("CONFIG_PATH") {
+            Ok(path) => (PathBuf::from(path), true),
+            Err(_) => (PathBuf::from(DEFAULT_CONFIG_PATH), false),
+        };
+
+        let file = match fs::read_to_string(&path) {
+            Ok(content) => parse_table(&content, &path.display().to_string())?,
+            // 既定のパスにファイルがなければ環境変数だけで設定する
+            Err(error) if error.kind() == std::io::ErrorKind::NotFound && !required => Table::new(),
+            Err(error) => {
+                return Err(AppError::Config(format!(
+                    "cannot read {}: {}",
+                    path.display(),
+                    error
+                )))
+            }
+        };
+
+        Self::build(file, |name| env::var(name).ok())
+    }
+
+    pub fn from_toml_str(content: &str) -> AppResult<Self> {
+        Self::build(parse_table(content, "config")?, |_| None)
+    }
+
+    fn build(file: Table, env: impl Fn(&str) -> Option<String>) -> AppResult<Self> {
+        let mut table = parse_table(DEFAULT_CONFIG, "default config")?;
+        merge(&mut table, file);
+        apply_env_overrides(&mut table, env)?;
+
+        let mut config: Config = serde_path_to_error::deserialize(table).map_err(|error| {
+            let path = error.path().to_string();
+            AppError::Config(format!("{}: {}", path, error.into_inner().message()))
+        })?;
+        config.validate()?;
+        Ok(config)
+    }
+
+    fn validate(&mut self) -> AppResult<()> {
+        let mut problems = Vec::new();
+        let mut require = |ok: bool, key: &str, message: &str| {
+            if !ok {
+                problems.push(format!("{}: {}", key, message));
+            }
+        };
+
+        require(
+            self.server.bind_address.parse::<SocketAddr>().is_ok(),
+            "server.bind_address",
+            "must be a socket address such as 0.0.0.0:8080",
+        );
+        for (key, value) in [
+            ("notion.api_key", &self.notion.api_key),
+            ("diary.database_id", &self.diary.database_id),
+            ("report.database_id", &self.report.database_id),
+        ] {
+            require(!value.trim().is_empty(), key, "must not be empty");
+        }
+        for (kind, database_id) in &self.report.databases {
+            require(
+                !database_id.trim().is_empty(),
+                &format!("report.databases.{}", kind.as_str()),
+                "must not be empty",
+            );
+        }
+        require(
+            !self.gemini.api_key.trim().is_empty()
+                || self
+                    .automations
+                    .iter()
+                    .all(|automation| automation.provider != LlmProviderKind::Gemini),
+            "gemini.api_key",
+            "must be set when an automation uses the gemini provider",
+        );
+        require(
+            self.notion.fetch_concurrency >= 1,
+            "notion.fetch_concurrency",
+            "must be at least 1",
+        );
+        require(
+            self.notion.requests_per_second > 0.0,
+            "notion.requests_per_second",
+            "must be greater than 0",
+        );
+        require(
+            self.http.max_retry_attempts >= 1,
+            "http.max_retry_attempts",
+            "must be at least 1",
+        );
+        require(
+            self.jobs.max_attempts >= 1,
+            "jobs.max_attempts",
+            "must be at least 1",
+        );
+        require(
+            self.jobs.worker_concurrency >= 1,
+            "jobs.worker_concurrency",
+            "must be at least 1",
+        );
+        require(
+            self.jobs.dedup_window_seconds >= 0,
+            "jobs.dedup_window_seconds",
+            "must not be negative",
+        );
+        for (index, schedule) in self.scheduler.schedules.iter().enumerate() {
+            require(
+                !schedule.name.trim().is_empty()
+                    && self.scheduler.schedules[..index]
+                        .iter()
+                        .all(|other| other.name != schedule.name),
+                &format!("scheduler.schedules[{}].name", index),
+                "must be unique and not empty",
+            );
+            require(
+                !schedule.page_id.trim().is_empty(),
+                &format!("scheduler.schedules[{}].page_id", index),
+                "must not be empty",
+            );
+        }
+
+        for (name, automation, default_prompt) in [
+            (
+                "diary",
+                &mut self.automations.diary,
+                include_str!("prompts/diary_review.txt").to_string(),
+            ),
+            (
+                "review",
+                &mut self.automations.review,
+                include_str!("prompts/review_prompt.txt").to_string(),
+            ),
+            (
+                "weekly_report",
+                &mut self.automations.weekly_report,
+                report_prompt(ReportPeriodKind::Week),
+            ),
+            (
+                "monthly_report",
+                &mut self.automations.monthly_report,
+                report_prompt(ReportPeriodKind::Month),
+            ),
+            (
+                "quarterly_report",
+                &mut self.automations.quarterly_report,
+                report_prompt(ReportPeriodKind::Quarter),
+            ),
+            (
+                "yearly_report",
+                &mut self.automations.yearly_report,
+                report_prompt(ReportPeriodKind::Year),
+            ),
+        ] {
+            require(
+                !automation.model.trim().is_empty(),
+                &format!("automations.{}.model", name),
+                "must not be empty",
+            );
+            require(
+                (0.0..=2.0).contains(&automation.temperature),
+                &format!("automations.{}.temperature", name),
+                "must be between 0.0 and 2.0",
+            );
+
+            if let Some(budget) = &automation.budget {
+                require(
+                    budget.max_chars.is_some() || budget.max_tokens.is_some(),
+                    &format!("automations.{}.budget", name),
+                    "must set max_chars or max_tokens",
+                );
+                require(
+                    budget.chunk_chars >= 1
+                        && budget
+                            .max_chars
+                            .is_none_or(|max_chars| budget.chunk_chars <= max_chars),
+                    &format!("automations.{}.budget.chunk_chars", name),
+                    "must be between 1 and max_chars",
+                );
+            }
+
+            automation.system_prompt = match &automation.prompt_path {
+                Some(path) => match fs::read_to_string(path) {
+                    Ok(prompt) => prompt,
+                    Err(error) => {
+                        require(
+                            false,
+                            &format!("automations.{}.prompt_path", name),
+                            &format!("cannot read {}: {}", path.display(), error),
+                        );
+                        continue;
+                    }
+                },
+                None => default_prompt,
+            };
+        }
+
+        // 空のトークンは未設定として扱う
+        for token in [
+            &mut self.server.webhook_verification_token,
+            &mut self.server.admin_token,
+        ] {
+            *token = token
+                .take()
+                .map(|token| token.trim().to_string())
+                .filter(|token| !token.is_empty());
+        }
+
+        if problems.is_empty() {
+            Ok(())
+        } else {
+            Err(AppError::Config(problems.join("; ")))
+        }
+    }
+}
+
+/// 期間の種類に合わせた既定のレポート用プロンプト
+fn report_prompt(kind: ReportPeriodKind) -> String {
+    let mut input = "日記の内容を".to_string();
+    if let Some(finer) = kind.finer() {
+        input = format!(
+            "入力は対象期間の{}（見つからない場合は日記）です。\
+             個々の出来事を繰り返すのではなく、期間全体を通した変化や傾向を",
+            finer.title_prefix()
+        );
+    }
+    let words: [(&str, &str); 8] = match kind {
+        ReportPeriodKind::Week => [
+            ("{span}", "1週間"),
+            ("{source}", "日記"),
+            ("{report}", "週次レポート"),
+            ("{this}", "今週"),
+            ("{scope}", "1週間"),
+            ("{trend}", ""),
+            ("{next}", "来週"),
+            ("{title}", "今週"),
+        ],
+        ReportPeriodKind::Month => [
+            ("{span}", "1か月"),
+            ("{source}", "記録"),
+            ("{report}", "月次レポート"),
+            ("{this}", "今月"),
+            ("{scope}", "期間"),
+            ("{trend}", "と、その推移"),
+            ("{next}", "来月"),
+            ("{title}", "今月"),
+        ],
+        ReportPeriodKind::Quarter => [
+            ("{span}", "3か月（四半期）"),
+            ("{source}", "記録"),
+            ("{report}", "四半期レポート"),
+            ("{this}", "この四半期"),
+            ("{scope}", "期間"),
+            ("{trend}", "と、その推移"),
+            ("{next}", "次の四半期"),
+            ("{title}", "四半期"),
+        ],
+        ReportPeriodKind::Year => [
+            ("{span}", "1年"),
+            ("{source}", "記録"),
+            ("{report}", "年次レポート"),
+            ("{this}", "今年"),
+            ("{scope}", "期間"),
+            ("{trend}", "と、その推移"),
+            ("{next}", "来年"),
+            ("{title}", "1年"),
+        ],
+    };
+    words.iter().fold(
+        REPORT_PROMPT_TEMPLATE.replace("{input}", &input),
+        |prompt, (placeholder, word)| prompt.replace(placeholder, word),
+    )
+}
+
+fn parse_table(content: &str, source: &str) -> AppResult<Table> {
+    content
+        .parse::<Table>()
+        .map_err(|error| AppError::Config(format!("{}: {}", source, error)))
+}
+
+// overlay の値で base を上書きする。テーブル同士は再帰的にマージする
+fn merge(base: &mut Table, overlay: Table) {
+    for (key, value) in overlay {
+        match (base.get_mut(&key), value) {
+            (Some(Value::Table(base)), Value::Table(overlay)) => merge(base, overlay),
+            (_, value) => {
+                base.insert(key, value);
+            }
+        }
+    }
+}
+
+fn apply_env_overrides(table: &mut Table, env: impl Fn(&str) -> Option<String>) -> AppResult<()> {
+    // Cloud Run などが渡す PORT は待ち受けアドレスのポートとして扱う
+    if let Some(port) = env("PORT") {
+        set_key(
+            table,
+            "server.bind_address",
+            Value::String(format!("0.0.0.0:{}", port.trim())),
+        );
+    }
+
+    for (name, key, kind) in ENV_OVERRIDES {
+        let Some(raw) = env(name) else {
+            continue;
+        };
+        let raw = raw.trim();
+        let value = match kind {
+            ValueKind::String => Some(Value::String(raw.to_string())),
+            ValueKind::Integer => raw.parse().ok().map(Value::Integer),
+            ValueKind::Float => raw.parse().ok().map(Value::Float),
+        };
+        let Some(value) = value else {
+            return Err(AppError::Config(format!(
+                "{} (from {}): invalid number {:?}",
+                key, name, raw
+            )));
+        };
+        set_key(table, key, value);
+    }
+    Ok(())
+}
+
+fn set_key(table: &mut Table, key: &str, value: Value) {
+    let mut segments: Vec<&str> = key.split('.').collect();
+    let last = segments.pop().unwrap_or(key);
+    let mut current = table;
+    for segment in segments {
+        let entry = current
+            .entry(segment.to_string())
+            .or_insert_with(|| Value::Table(Table::new()));
+        if !entry.is_table() {
+            *entry = Value::Table(Table::new());
+        }
+        let Value::Table(next) = entry else {
+            unreachable!()
+        };
+        current = next;
+    }
+    current.insert(last.to_string(), value);
+}
+
+#[cfg(test)]
+pub(crate) fn test_config() -> Config {
+    Config::from_toml_str(
+        r#"
+        [notion]
+        api_key = "key"
+
+        [gemini]
+        api_key = "key"
+
+        [diary]
+        database_id = "diary"
+
+        [report]
+        database_id = "report"
+        "#,
+    )
+    .unwrap()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const REQUIRED: &str = r#"
+        [notion]
+        api_key = "key"
+        [gemini]
+        api_key = "key"
+        [diary]
+        database_id = "diary"
+        [report]
+        database_id = "report"
+    "#;
+
+    fn build(content: &str, env: &[(&str, &str)]) -> AppResult<Config> {
+        let file = parse_table(content, "test")?;
+        Config::build(file, |name| {
+            env.iter()
+                .find(|(key, _)| *key == name)
+                .map(|(_, value)| value.to_string())
+        })
+    }
+
+    #[test]
+    fn test_defaults_are_applied() {
+        let config = test_config();
+        assert_eq!(config.server.bind_address, "0.0.0.0:8080");
+        assert_eq!(config.diary.properties.date, "日付");
+        assert_eq!(config.automations.review.model, "gemini-3-pro-preview");
+        assert_eq!(config.automations.diary.temperature, 0.8);
+        assert!(!config.automations.diary.system_prompt.is_empty());
+        assert_eq!(config.server.webhook_verification_token, None);
+        assert_eq!(config.server.admin_token, None);
+        assert!(config.automations.iter().all(|automation| automation.budget.is_none()));
+    }
+
+    #[test]
+    fn test_file_values_and_env_overrides() {
+        let config = build(
+            &format!(
+                "{}\n[automations.diary]\ntemperature = 0.2\n[diary.properties]\ndate = \"Date\"\n",
+                REQUIRED.replace("[notion]", "[notion]\nmax_block_depth = 1")
+            ),
+            &[
+                ("PORT", "3000"),
+                ("NOTION_API_KEY", " from-env "),
+                ("NOTION_REQUESTS_PER_SECOND", "2"),
+                ("NOTION_FETCH_CONCURRENCY", "8"),
+                ("ADMIN_TOKEN", "admin"),
+            ],
+        )
+        .unwrap();
+        assert_eq!(config.automations.diary.temperature, 0.2);
+        // 省略したキーは既定値のまま
+        assert_eq!(config.automations.diary.model, "gemini-3-flash-preview");
+        assert_eq!(config.diary.properties.date, "Date");
+        assert_eq!(config.diary.properties.title, "名前");
+        assert_eq!(config.notion.max_block_depth, 1);
+        assert_eq!(config.notion.api_key, "from-env");
+        assert_eq!(config.notion.requests_per_second, 2.0);
+        assert_eq!(config.notion.fetch_concurrency, 8);
+        assert_eq!(config.server.bind_address, "0.0.0.0:3000");
+        assert_eq!(config.server.admin_token.as_deref(), Some("admin"));
+    }
+
+    #[test]
+    fn test_gemini_key_is_only_required_for_gemini_automations() {
+        let local_only = REQUIRED.replace("[gemini]\n        api_key = \"key\"", "")
+            + r#"
+            [automations.diary]
+            provider = "ollama"
+            model = "llama3.1"
+            [automations.review]
+            provider = "openai"
+            [automations.weekly_report]
+            provider = "ollama"
+            [automations.monthly_report]
+            provider = "ollama"
+            [automations.quarterly_report]
+            provider = "ollama"
+            [automations.yearly_report]
+            provider = "ollama"
+        "#;
+        let config = build(&local_only, &[]).unwrap();
+        assert_eq!(config.automations.diary.provider, LlmProviderKind::Ollama);
+        assert_eq!(config.automations.review.provider, LlmProviderKind::OpenAi);
+
+        let error = build(
+            &local_only.replace("provider = \"openai\"", "provider = \"gemini\""),
+            &[],
+        )
+        .unwrap_err()
+        .to_string();
+        assert!(error.contains("gemini.api_key: must be set"), "{error}");
+    }
+
+    #[test]
+    fn test_errors_name_the_key() {
+        let error = build("", &[]).unwrap_err().to_string();
+        assert!(
+            error.contains("diary: missing field `database_id`"),
+            "{error}"
+        );
+
+        let error = build(
+            &format!("{}\n[jobs]\nmax_attempts = \"three\"", REQUIRED),
+            &[],
+        )
+        .unwrap_err()
+        .to_string();
+        assert!(error.contains("jobs.max_attempts"), "{error}");
+
+        let error = build(&format!("{}\n[jobs]\nmax_atempts = 3", REQUIRED), &[])
+            .unwrap_err()
+            .to_string();
+        assert!(error.contains("max_atempts"), "{error}");
+
+        let error = build(REQUIRED, &[("JOB_WORKER_CONCURRENCY", "many")])
+            .unwrap_err()
+            .to_string();
+        assert!(
+            error.contains("jobs.worker_concurrency (from JOB_WORKER_CONCURRENCY)"),
+            "{error}"
+        );
+
+        let error = build(
+            &format!("{}\n[automations.review]\ntemperature = 3.0", REQUIRED),
+            &[("NOTION_API_KEY", "")],
+        )
+        .unwrap_err()
+        .to_string();
+        assert!(
+            error.contains("notion.api_key: must not be empty"),
+            "{error}"
+        );
+        assert!(
+            error.contains("automations.review.temperature: must be between 0.0 and 2.0"),
+            "{error}"
+        );
+
+        let error = build(
+            &format!(
+                "{}\n[automations.weekly_report.budget]\nmax_chars = 60000\nchunk_chars = 100000",
+                REQUIRED
+            ),
+            &[],
+        )
+        .unwrap_err()
+        .to_string();
+        assert!(
+            error.contains("automations.weekly_report.budget.chunk_chars"),
+            "{error}"
+        );
+    }
+
+    #[test]
+    fn test_schedules() {
+        let config = build(
+            &format!(
+                "{}\n[calendar]\ntimezone = \"Asia/Tokyo\"\n[[scheduler.schedules]]\nname = \"weekly\"\ncron = \"0 9 * * MON\"\nautomation = \"weekly_report\"\npage_id = \"page\"",
+                REQUIRED
+            ),
+            &[],
+        )
+        .unwrap();
+        assert_eq!(config.calendar.timezone, chrono_tz::Asia::Tokyo);
+        assert_eq!(config.calendar.week_alignment, WeekAlignment::Rolling);
+        assert_eq!(
+            config.scheduler.schedules[0].automation,
+            JobKind::WeeklyReport
+        );
+
+        let config = build(REQUIRED, &[("REPORT_WEEK_ALIGNMENT", "iso")]).unwrap();
+        assert_eq!(config.calendar.week_alignment, WeekAlignment::Iso);
+
+        let error = build(REQUIRED, &[("TIMEZONE", "Mars/Olympus")])
+            .unwrap_err()
+            .to_string();
+        assert!(error.contains("calendar.timezone"), "{error}");
+
+        let error = build(
+            &format!(
+                "{}\n[[scheduler.schedules]]\nname = \"weekly\"\ncron = \"every monday\"\nautomation = \"weekly_report\"\npage_id = \"page\"",
+                REQUIRED
+            ),
+            &[],
+        )
+        .unwrap_err()
+        .to_string();
+        assert!(error.contains("scheduler.schedules[0].cron"), "{error}");
+    }
+
+    #[test]
+    fn test_report_databases_per_period() {
+        let config = build(
+            &format!("{}\n[report.databases]\nmonth = \"monthly\"", REQUIRED),
+            &[],
+        )
+        .unwrap();
+        assert_eq!(
+            config.report.databases.get(&ReportPeriodKind::Month),
+            Some(&"monthly".to_string())
+        );
+        assert!(config
+            .automations
+            .for_period(ReportPeriodKind::Year)
+            .system_prompt
+            .contains("年次レポート"));
+
+        let error = build(
+            &format!("{}\n[report.databases]\ndecade = \"x\"", REQUIRED),
+            &[],
+        )
+        .unwrap_err()
+        .to_string();
+        assert!(error.contains("report.databases"), "{error}");
+    }
+
+    #[test]
+    fn test_report_prompts_fill_every_placeholder() {
+        for kind in [
+            ReportPeriodKind::Week,
+            ReportPeriodKind::Month,
+            ReportPeriodKind::Quarter,
+            ReportPeriodKind::Year,
+        ] {
+            let prompt = report_prompt(kind);
+            assert!(!prompt.contains("{span}") && !prompt.contains("{this}"));
+            assert!(!prompt.lines().next().unwrap().contains('{'), "{prompt}");
+        }
+        assert!(report_prompt(ReportPeriodKind::Week).starts_with(
+            "あなたは、ユーザーの1週間分の日記を読み解き、Notion形式で「週次レポート」を"
+        ));
+        assert!(report_prompt(ReportPeriodKind::Year).contains("入力は対象期間の四半期報"));
+    }
+}
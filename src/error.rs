@@ -0,0 +1,151 @@
+use reqwest::StatusCode;
+
+use crate::types::{GeminiAPIErrorResponse, NotionErrorResponse};
+
+pub type AppResult<T> = Result<T, AppError>;
+
+#[derive(Debug, thiserror::Error)]
+pub enum AppError {
+    #[error("Notion API error: status {status}, code {code}: {message}")]
+    NotionApi {
+        status: StatusCode,
+        code: String,
+        message: String,
+    },
+    #[error("Gemini API error: status {status}: {message}")]
+    GeminiApi { status: StatusCode, message: String },
+    #[error("Gemini blocked the response: {reason}")]
+    GeminiBlocked { reason: String },
+    #[error("Gemini returned no content")]
+    GeminiEmptyResponse,
+    #[error("{provider} API error: status {status}: {message}")]
+    LlmApi {
+        provider: &'static str,
+        status: StatusCode,
+        message: String,
+    },
+    #[error("{provider} returned no content")]
+    LlmEmptyResponse { provider: &'static str },
+    #[error("HTTP request failed: {0}")]
+    Http(#[from] reqwest::Error),
+    #[error("Serialization error: {0}")]
+    Serialization(#[from] serde_json::Error),
+    #[error("Database error: {0}")]
+    Database(#[from] rusqlite::Error),
+    #[error("Configuration error: {0}")]
+    Config(String),
+    #[error("Invalid payload: {0}")]
+    InvalidPayload(String),
+    #[error("Could not fetch any of the {count} source pages")]
+    SourcePagesUnavailable { count: usize },
+}
+
+impl AppError {
+    // Notion のエラーボディ {"object":"error","code":...,"message":...} を読み取る
+    pub fn notion_api(status: StatusCode, body: &str) -> Self {
+        match serde_json::from_str::<NotionErrorResponse>(body) {
+            Ok(error) => Self::NotionApi {
+                status,
+                code: error.code,
+                message: error.message,
+            },
+            Err(_) => Self::NotionApi {
+                status,
+                code: "unknown".to_string(),
+                message: body.to_string(),
+            },
+        }
+    }
+
+    // Gemini のエラーボディ {"error":{"code":...,"message":...,"status":...}} を読み取る
+    pub fn gemini_api(status: StatusCode, body: &str) -> Self {
+        let message = serde_json::from_str::<GeminiAPIErrorResponse>(body)
+            .map(|response| response.error.message)
+            .unwrap_or_else(|_| body.to_string());
+        Self::GeminiApi { status, message }
+    }
+
+    pub fn status(&self) -> Option<StatusCode> {
+        match self {
+            Self::NotionApi { status, .. }
+            | Self::GeminiApi { status, .. }
+            | Self::LlmApi { status, .. } => Some(*status),
+            Self::Http(error) => error.status(),
+            _ => None,
+        }
+    }
+
+    pub fn is_not_found(&self) -> bool {
+        self.status() == Some(StatusCode::NOT_FOUND)
+    }
+
+    /// 時間をおいて再実行すれば成功する可能性があるエラーか
+    pub fn is_retryable(&self) -> bool {
+        match self {
+            Self::Http(error) if error.is_timeout() || error.is_connect() => true,
+            // 一時的な障害で全ページが取れなかった可能性が高い
+            Self::SourcePagesUnavailable { .. } => true,
+            _ => self.status().is_some_and(|status| {
+                status == StatusCode::TOO_MANY_REQUESTS || status.is_server_error()
+            }),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_notion_api_error_parses_body() {
+        let error = AppError::notion_api(
+            StatusCode::NOT_FOUND,
+            r#"{"object":"error","status":404,"code":"object_not_found","message":"Could not find block"}"#,
+        );
+        match &error {
+            AppError::NotionApi { code, message, .. } => {
+                assert_eq!(code, "object_not_found");
+                assert_eq!(message, "Could not find block");
+            }
+            other => panic!("unexpected error: {other:?}"),
+        }
+        assert!(error.is_not_found());
+        assert!(!error.is_retryable());
+    }
+
+    #[test]
+    fn test_notion_api_error_with_unparsable_body() {
+        let error = AppError::notion_api(StatusCode::BAD_GATEWAY, "<html>Bad Gateway</html>");
+        match &error {
+            AppError::NotionApi { code, message, .. } => {
+                assert_eq!(code, "unknown");
+                assert_eq!(message, "<html>Bad Gateway</html>");
+            }
+            other => panic!("unexpected error: {other:?}"),
+        }
+        assert!(error.is_retryable());
+    }
+
+    #[test]
+    fn test_gemini_api_error_parses_body() {
+        let error = AppError::gemini_api(
+            StatusCode::TOO_MANY_REQUESTS,
+            r#"{"error":{"code":429,"message":"Resource has been exhausted","status":"RESOURCE_EXHAUSTED"}}"#,
+        );
+        assert_eq!(
+            error.to_string(),
+            "Gemini API error: status 429 Too Many Requests: Resource has been exhausted"
+        );
+        assert!(error.is_retryable());
+    }
+
+    #[test]
+    fn test_unavailable_source_pages_are_retryable() {
+        let error = AppError::SourcePagesUnavailable { count: 3 };
+        assert_eq!(
+            error.to_string(),
+            "Could not fetch any of the 3 source pages"
+        );
+        assert!(error.is_retryable());
+    }
+}
@@ -0,0 +1,711 @@
+pub mod handlers;
+pub mod scheduler;
+pub mod worker;
+
+use std::sync::Arc;
+
+use chrono::{DateTime, Duration, Utc};
+use rusqlite::{params, types::Type, Connection, OptionalExtension, Row};
+use serde::{Deserialize, Serialize};
+use tokio::sync::Notify;
+
+use crate::{
+    automation::AutomationOutcome,
+    error::{AppError, AppResult},
+    store::Store,
+    types::NotionWebhookPayload,
+};
+
+pub const DEFAULT_MAX_ATTEMPTS: u32 = 3;
+// 自動化ペイロード (イベント ID なし) を同一イベントとみなす時間幅
+pub const DEFAULT_DEDUP_WINDOW_SECONDS: i64 = 300;
+// イベント ID による重複排除の記録を保持する期間
+const DEDUP_RETENTION_DAYS: i64 = 7;
+// リトライ時の待ち時間の基準。試行ごとに倍にする
+const RETRY_BASE_DELAY_SECONDS: i64 = 30;
+
+pub const DEFAULT_LIST_LIMIT: u32 = 50;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum JobKind {
+    Diary,
+    Review,
+    WeeklyReport,
+    PeriodicReport,
+}
+
+impl JobKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Diary => "diary",
+            Self::Review => "review",
+            Self::WeeklyReport => "weekly_report",
+            Self::PeriodicReport => "periodic_report",
+        }
+    }
+
+    fn parse(value: &str) -> Option<Self> {
+        match value {
+            "diary" => Some(Self::Diary),
+            "review" => Some(Self::Review),
+            "weekly_report" => Some(Self::WeeklyReport),
+            "periodic_report" => Some(Self::PeriodicReport),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum JobState {
+    Queued,
+    Running,
+    Succeeded,
+    // リトライ上限に達した、またはリトライしても成功しないジョブ (dead letter)
+    Failed,
+}
+
+impl JobState {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Queued => "queued",
+            Self::Running => "running",
+            Self::Succeeded => "succeeded",
+            Self::Failed => "failed",
+        }
+    }
+
+    fn parse(value: &str) -> Option<Self> {
+        match value {
+            "queued" => Some(Self::Queued),
+            "running" => Some(Self::Running),
+            "succeeded" => Some(Self::Succeeded),
+            "failed" => Some(Self::Failed),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Job {
+    pub id: i64,
+    pub kind: JobKind,
+    pub page_id: Option<String>,
+    pub payload: serde_json::Value,
+    pub state: JobState,
+    pub attempts: u32,
+    pub max_attempts: u32,
+    #[serde(rename = "error")]
+    pub last_error: Option<String>,
+    pub model: Option<String>,
+    pub appended_block_ids: Vec<String>,
+    // 本文を取得できず、結果に含められなかったページ
+    pub failed_page_ids: Vec<String>,
+    pub run_after: DateTime<Utc>,
+    pub created_at: DateTime<Utc>,
+    pub started_at: Option<DateTime<Utc>>,
+    pub finished_at: Option<DateTime<Utc>>,
+    pub updated_at: DateTime<Utc>,
+}
+
+impl Job {
+    fn from_row(row: &Row) -> rusqlite::Result<Self> {
+        let kind: String = row.get("kind")?;
+        let state: String = row.get("state")?;
+        let payload: String = row.get("payload")?;
+        let appended_block_ids: Option<String> = row.get("appended_block_ids")?;
+        let failed_page_ids: Option<String> = row.get("failed_page_ids")?;
+        Ok(Self {
+            id: row.get("id")?,
+            kind: JobKind::parse(&kind).ok_or_else(|| invalid_column("kind", kind))?,
+            page_id: row.get("page_id")?,
+            payload: parse_json_column(&payload)?,
+            state: JobState::parse(&state).ok_or_else(|| invalid_column("state", state))?,
+            attempts: row.get("attempts")?,
+            max_attempts: row.get("max_attempts")?,
+            last_error: row.get("last_error")?,
+            model: row.get("model")?,
+            appended_block_ids: match appended_block_ids {
+                Some(ids) => parse_json_column(&ids)?,
+                None => vec![],
+            },
+            failed_page_ids: match failed_page_ids {
+                Some(ids) => parse_json_column(&ids)?,
+                None => vec![],
+            },
+            run_after: row.get("run_after")?,
+            created_at: row.get("created_at")?,
+            started_at: row.get("started_at")?,
+            finished_at: row.get("finished_at")?,
+            updated_at: row.get("updated_at")?,
+        })
+    }
+}
+
+pub(crate) fn parse_json_column<T: serde::de::DeserializeOwned>(
+    value: &str,
+) -> rusqlite::Result<T> {
+    serde_json::from_str(value)
+        .map_err(|e| rusqlite::Error::FromSqlConversionFailure(0, Type::Text, Box::new(e)))
+}
+
+// Notion の ID はハイフンの有無が混在するため、検索用に正規化して保存する
+pub fn normalize_page_id(page_id: &str) -> String {
+    page_id.replace('-', "").to_lowercase()
+}
+
+fn invalid_column(column: &str, value: String) -> rusqlite::Error {
+    rusqlite::Error::InvalidColumnName(format!("{column}={value}"))
+}
+
+/// 同じ論理イベントを判定するためのキー
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DedupKey {
+    // Notion が付与したイベント ID。再送されても同じ値になる
+    Event(String),
+    // イベント ID のない自動化ペイロードは、ページ単位で一定時間内の重複を弾く
+    PageWindow(String),
+}
+
+impl DedupKey {
+    pub fn for_payload(kind: JobKind, payload: &NotionWebhookPayload) -> Self {
+        // 同じページでも期間の種類や日付の違うレポートは別のイベントとして扱う
+        let mut scope = kind.as_str().to_string();
+        if let Some(period) = &payload.period {
+            if let Some(period_kind) = period.kind {
+                scope.push_str(&format!(":{}", period_kind.as_str()));
+            }
+            if let Some(start) = period.start {
+                scope.push_str(&format!(":from:{}", start));
+            }
+            if let Some(end) = period.end {
+                scope.push_str(&format!(":to:{}", end));
+            }
+        }
+        match payload.event_id() {
+            Some(event_id) => Self::Event(format!("{}:event:{}", scope, event_id)),
+            None => Self::PageWindow(format!(
+                "{}:page:{}",
+                scope,
+                normalize_page_id(&payload.data.id)
+            )),
+        }
+    }
+
+    fn as_str(&self) -> &str {
+        match self {
+            Self::Event(key) | Self::PageWindow(key) => key,
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct EnqueuedJob {
+    pub job: Job,
+    // 既存のジョブを返した場合は true
+    pub duplicate: bool,
+}
+
+/// SQLite に永続化されるジョブキュー。webhook はここに積むだけで、
+/// 実際の処理は worker が取り出して実行する
+#[derive(Clone)]
+pub struct JobQueue {
+    store: Store,
+    notify: Arc<Notify>,
+    max_attempts: u32,
+    dedup_window: Duration,
+}
+
+impl JobQueue {
+    pub fn new(store: Store) -> Self {
+        Self {
+            store,
+            notify: Arc::new(Notify::new()),
+            max_attempts: DEFAULT_MAX_ATTEMPTS,
+            dedup_window: Duration::seconds(DEFAULT_DEDUP_WINDOW_SECONDS),
+        }
+    }
+
+    pub fn with_max_attempts(mut self, max_attempts: u32) -> Self {
+        self.max_attempts = max_attempts.max(1);
+        self
+    }
+
+    pub fn with_dedup_window(mut self, dedup_window: Duration) -> Self {
+        self.dedup_window = dedup_window;
+        self
+    }
+
+    pub fn enqueue(&self, kind: JobKind, payload: &NotionWebhookPayload) -> AppResult<Job> {
+        let job = insert_job(&self.store.connection(), kind, payload, self.max_attempts)?;
+        self.notify.notify_one();
+        Ok(job)
+    }
+
+    /// 同じイベントのジョブがすでにあればそれを返し、なければ新しく積む
+    pub fn enqueue_once(
+        &self,
+        kind: JobKind,
+        payload: &NotionWebhookPayload,
+    ) -> AppResult<EnqueuedJob> {
+        let key = DedupKey::for_payload(kind, payload);
+        let now = Utc::now();
+
+        let mut connection = self.store.connection();
+        let transaction = connection.transaction()?;
+        transaction.execute(
+            "DELETE FROM dedup_keys WHERE created_at < ?1",
+            [now - Duration::days(DEDUP_RETENTION_DAYS)],
+        )?;
+
+        let existing: Option<(i64, DateTime<Utc>)> = transaction
+            .query_row(
+                "SELECT job_id, created_at FROM dedup_keys WHERE key = ?1",
+                [key.as_str()],
+                |row| Ok((row.get(0)?, row.get(1)?)),
+            )
+            .optional()?;
+        let existing_job_id = existing.and_then(|(job_id, created_at)| match key {
+            DedupKey::Event(_) => Some(job_id),
+            DedupKey::PageWindow(_) => (created_at >= now - self.dedup_window).then_some(job_id),
+        });
+        if let Some(job_id) = existing_job_id {
+            let job = transaction
+                .query_row("SELECT * FROM jobs WHERE id = ?1", [job_id], Job::from_row)
+                .optional()?;
+            if let Some(job) = job {
+                return Ok(EnqueuedJob {
+                    job,
+                    duplicate: true,
+                });
+            }
+        }
+
+        let job = insert_job(&transaction, kind, payload, self.max_attempts)?;
+        transaction.execute(
+            "INSERT OR REPLACE INTO dedup_keys (key, job_id, created_at) VALUES (?1, ?2, ?3)",
+            params![key.as_str(), job.id, now],
+        )?;
+        transaction.commit()?;
+
+        self.notify.notify_one();
+        Ok(EnqueuedJob {
+            job,
+            duplicate: false,
+        })
+    }
+
+    /// 定期実行の予定時刻 due_at がまだ実行済みでなければジョブを積み、実行済みとして記録する。
+    /// 初めて見る予定は run_first のときだけ due_at の分を実行し、それ以外は記録するだけで
+    /// 過去の分はさかのぼらない
+    pub fn enqueue_scheduled(
+        &self,
+        name: &str,
+        due_at: DateTime<Utc>,
+        run_first: bool,
+        kind: JobKind,
+        payload: &NotionWebhookPayload,
+    ) -> AppResult<Option<Job>> {
+        let mut connection = self.store.connection();
+        let transaction = connection.transaction()?;
+        let last_run_at: Option<DateTime<Utc>> = transaction
+            .query_row(
+                "SELECT last_run_at FROM schedule_runs WHERE name = ?1",
+                [name],
+                |row| row.get(0),
+            )
+            .optional()?;
+
+        let job = match last_run_at {
+            Some(last_run_at) if due_at <= last_run_at => None,
+            None if !run_first => {
+                transaction.execute(
+                    "INSERT INTO schedule_runs (name, last_run_at) VALUES (?1, ?2)",
+                    params![name, due_at],
+                )?;
+                None
+            }
+            _ => {
+                let job = insert_job(&transaction, kind, payload, self.max_attempts)?;
+                transaction.execute(
+                    "INSERT INTO schedule_runs (name, last_run_at) VALUES (?1, ?2)
+                     ON CONFLICT (name) DO UPDATE SET last_run_at = excluded.last_run_at",
+                    params![name, due_at],
+                )?;
+                Some(job)
+            }
+        };
+        transaction.commit()?;
+
+        if job.is_some() {
+            self.notify.notify_one();
+        }
+        Ok(job)
+    }
+
+    pub fn get(&self, id: i64) -> AppResult<Option<Job>> {
+        Ok(self
+            .store
+            .connection()
+            .query_row("SELECT * FROM jobs WHERE id = ?1", [id], Job::from_row)
+            .optional()?)
+    }
+
+    /// ページに紐づくジョブを新しい順に返す
+    pub fn list_by_page(&self, page_id: &str, limit: u32) -> AppResult<Vec<Job>> {
+        let connection = self.store.connection();
+        let mut statement = connection
+            .prepare("SELECT * FROM jobs WHERE page_id = ?1 ORDER BY id DESC LIMIT ?2")?;
+        let jobs = statement
+            .query_map(params![normalize_page_id(page_id), limit], Job::from_row)?
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(jobs)
+    }
+
+    pub fn list_recent(&self, limit: u32) -> AppResult<Vec<Job>> {
+        let connection = self.store.connection();
+        let mut statement = connection.prepare("SELECT * FROM jobs ORDER BY id DESC LIMIT ?1")?;
+        let jobs = statement
+            .query_map([limit], Job::from_row)?
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(jobs)
+    }
+
+    /// 実行可能なジョブを 1 件取り出し、running にする
+    pub fn claim_next(&self) -> AppResult<Option<Job>> {
+        let now = Utc::now();
+        Ok(self
+            .store
+            .connection()
+            .query_row(
+                "UPDATE jobs SET state = ?1, attempts = attempts + 1, started_at = ?2, updated_at = ?2
+                 WHERE id = (
+                     SELECT id FROM jobs WHERE state = ?3 AND run_after <= ?2 ORDER BY id LIMIT 1
+                 )
+                 RETURNING *",
+                params![JobState::Running.as_str(), now, JobState::Queued.as_str()],
+                Job::from_row,
+            )
+            .optional()?)
+    }
+
+    pub fn mark_succeeded(&self, id: i64, outcome: &AutomationOutcome) -> AppResult<()> {
+        self.store.connection().execute(
+            "UPDATE jobs SET state = ?1, last_error = NULL, model = ?2, appended_block_ids = ?3,
+                 failed_page_ids = ?4, finished_at = ?5, updated_at = ?5
+             WHERE id = ?6",
+            params![
+                JobState::Succeeded.as_str(),
+                outcome.model,
+                serde_json::to_string(&outcome.appended_block_ids)?,
+                serde_json::to_string(&outcome.failed_page_ids)?,
+                Utc::now(),
+                id
+            ],
+        )?;
+        Ok(())
+    }
+
+    /// 失敗を記録する。リトライ可能で試行回数が残っていれば、時間をおいて再度 queued にする
+    pub fn mark_failed(&self, job: &Job, error: &AppError) -> AppResult<JobState> {
+        let now = Utc::now();
+        let (state, run_after, finished_at) =
+            if error.is_retryable() && job.attempts < job.max_attempts {
+                let delay = RETRY_BASE_DELAY_SECONDS << job.attempts.saturating_sub(1).min(10);
+                (JobState::Queued, now + Duration::seconds(delay), None)
+            } else {
+                (JobState::Failed, job.run_after, Some(now))
+            };
+
+        self.store.connection().execute(
+            "UPDATE jobs SET state = ?1, last_error = ?2, run_after = ?3, finished_at = ?4, updated_at = ?5
+             WHERE id = ?6",
+            params![
+                state.as_str(),
+                error.to_string(),
+                run_after,
+                finished_at,
+                now,
+                job.id
+            ],
+        )?;
+        Ok(state)
+    }
+
+    /// 前回のプロセスが実行中のまま終了したジョブを queued に戻す
+    pub fn recover_interrupted(&self) -> AppResult<usize> {
+        Ok(self.store.connection().execute(
+            "UPDATE jobs SET state = ?1, updated_at = ?2 WHERE state = ?3",
+            params![
+                JobState::Queued.as_str(),
+                Utc::now(),
+                JobState::Running.as_str()
+            ],
+        )?)
+    }
+
+    pub(crate) async fn wait_for_job(&self, timeout: std::time::Duration) {
+        let _ = tokio::time::timeout(timeout, self.notify.notified()).await;
+    }
+}
+
+fn insert_job(
+    connection: &Connection,
+    kind: JobKind,
+    payload: &NotionWebhookPayload,
+    max_attempts: u32,
+) -> AppResult<Job> {
+    let now = Utc::now();
+    let page_id = normalize_page_id(&payload.data.id);
+    let payload = serde_json::to_string(payload)?;
+    Ok(connection.query_row(
+        "INSERT INTO jobs (kind, page_id, payload, state, attempts, max_attempts, run_after, created_at, updated_at)
+         VALUES (?1, ?2, ?3, ?4, 0, ?5, ?6, ?6, ?6)
+         RETURNING *",
+        params![
+            kind.as_str(),
+            page_id,
+            payload,
+            JobState::Queued.as_str(),
+            max_attempts,
+            now
+        ],
+        Job::from_row,
+    )?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::NotionPageRef;
+    use reqwest::StatusCode;
+
+    fn payload(page_id: &str) -> NotionWebhookPayload {
+        NotionWebhookPayload {
+            id: None,
+            source: None,
+            data: NotionPageRef {
+                id: page_id.to_string(),
+            },
+            period: None,
+        }
+    }
+
+    fn test_queue() -> JobQueue {
+        JobQueue::new(Store::open_in_memory().unwrap())
+    }
+
+    #[test]
+    fn test_enqueue_and_claim() {
+        let queue = test_queue();
+        let job = queue.enqueue(JobKind::Diary, &payload("page-1")).unwrap();
+        assert_eq!(job.state, JobState::Queued);
+        assert_eq!(job.payload["data"]["id"], "page-1");
+
+        let claimed = queue.claim_next().unwrap().unwrap();
+        assert_eq!(claimed.id, job.id);
+        assert_eq!(claimed.state, JobState::Running);
+        assert_eq!(claimed.attempts, 1);
+        assert!(queue.claim_next().unwrap().is_none());
+
+        let outcome = AutomationOutcome {
+            model: "gemini-3-flash-preview".to_string(),
+            appended_block_ids: vec!["block-1".to_string()],
+            failed_page_ids: vec!["diary-1".to_string()],
+        };
+        queue.mark_succeeded(claimed.id, &outcome).unwrap();
+        let stored = queue.get(job.id).unwrap().unwrap();
+        assert_eq!(stored.state, JobState::Succeeded);
+        assert_eq!(stored.model.as_deref(), Some("gemini-3-flash-preview"));
+        assert_eq!(stored.appended_block_ids, vec!["block-1".to_string()]);
+        assert_eq!(stored.failed_page_ids, vec!["diary-1".to_string()]);
+        assert!(stored.started_at.is_some());
+        assert!(stored.finished_at.is_some());
+    }
+
+    #[test]
+    fn test_list_by_page_normalizes_ids() {
+        let queue = test_queue();
+        let first = queue
+            .enqueue(
+                JobKind::Diary,
+                &payload("1234abcd-0000-0000-0000-000000000000"),
+            )
+            .unwrap();
+        queue.enqueue(JobKind::Diary, &payload("other")).unwrap();
+        let second = queue
+            .enqueue(
+                JobKind::Review,
+                &payload("1234ABCD000000000000000000000000"),
+            )
+            .unwrap();
+
+        let jobs = queue
+            .list_by_page("1234abcd-0000-0000-0000-000000000000", DEFAULT_LIST_LIMIT)
+            .unwrap();
+        let ids: Vec<i64> = jobs.iter().map(|job| job.id).collect();
+        assert_eq!(ids, vec![second.id, first.id]);
+        assert_eq!(queue.list_recent(1).unwrap().len(), 1);
+    }
+
+    #[test]
+    fn test_enqueue_once_deduplicates_event_ids() {
+        let queue = test_queue();
+        let mut delivery = payload("page-1");
+        delivery.id = Some("event-1".to_string());
+
+        let first = queue.enqueue_once(JobKind::Diary, &delivery).unwrap();
+        let retried = queue.enqueue_once(JobKind::Diary, &delivery).unwrap();
+        assert!(!first.duplicate);
+        assert!(retried.duplicate);
+        assert_eq!(first.job.id, retried.job.id);
+
+        let mut next_event = payload("page-1");
+        next_event.id = Some("event-2".to_string());
+        let next = queue.enqueue_once(JobKind::Diary, &next_event).unwrap();
+        assert!(!next.duplicate);
+    }
+
+    #[test]
+    fn test_enqueue_once_deduplicates_within_window() {
+        let queue = test_queue();
+        let first = queue
+            .enqueue_once(JobKind::Review, &payload("page-1"))
+            .unwrap();
+        let double_fired = queue
+            .enqueue_once(JobKind::Review, &payload("page-1"))
+            .unwrap();
+        assert!(double_fired.duplicate);
+        assert_eq!(first.job.id, double_fired.job.id);
+
+        // 別の自動化や別のページは重複扱いしない
+        assert!(
+            !queue
+                .enqueue_once(JobKind::Diary, &payload("page-1"))
+                .unwrap()
+                .duplicate
+        );
+        assert!(
+            !queue
+                .enqueue_once(JobKind::Review, &payload("page-2"))
+                .unwrap()
+                .duplicate
+        );
+
+        // 同じページでも期間の違うレポートは別に積む
+        let mut monthly = payload("page-1");
+        monthly.period = Some(crate::period::ReportPeriodRequest {
+            kind: Some(crate::period::ReportPeriodKind::Month),
+            ..Default::default()
+        });
+        assert!(
+            !queue
+                .enqueue_once(JobKind::PeriodicReport, &payload("page-1"))
+                .unwrap()
+                .duplicate
+        );
+        assert!(
+            !queue
+                .enqueue_once(JobKind::PeriodicReport, &monthly)
+                .unwrap()
+                .duplicate
+        );
+
+        // 時間幅を過ぎたら新しいイベントとして扱う
+        let expired = Utc::now() - Duration::seconds(DEFAULT_DEDUP_WINDOW_SECONDS + 1);
+        queue
+            .store
+            .connection()
+            .execute("UPDATE dedup_keys SET created_at = ?1", [expired])
+            .unwrap();
+        let later = queue
+            .enqueue_once(JobKind::Review, &payload("page-1"))
+            .unwrap();
+        assert!(!later.duplicate);
+        assert_ne!(later.job.id, first.job.id);
+    }
+
+    #[test]
+    fn test_dedup_key_includes_period_dates() {
+        let monthly = |start: &str, end: &str| {
+            let mut report = payload("page-1");
+            report.period = Some(crate::period::ReportPeriodRequest {
+                kind: Some(crate::period::ReportPeriodKind::Month),
+                start: Some(start.parse().unwrap()),
+                end: Some(end.parse().unwrap()),
+            });
+            report
+        };
+        let january = monthly("2024-01-01", "2024-01-31");
+        assert_eq!(
+            DedupKey::for_payload(JobKind::PeriodicReport, &january),
+            DedupKey::PageWindow(
+                "periodic_report:month:from:2024-01-01:to:2024-01-31:page:page1".to_string()
+            )
+        );
+
+        // 同じページでも期間の違うレポートは別に積む
+        let queue = test_queue();
+        let enqueue = |report: &NotionWebhookPayload| {
+            queue
+                .enqueue_once(JobKind::PeriodicReport, report)
+                .unwrap()
+                .duplicate
+        };
+        assert!(!enqueue(&january));
+        assert!(!enqueue(&monthly("2024-02-01", "2024-02-29")));
+        assert!(enqueue(&monthly("2024-01-01", "2024-01-31")));
+    }
+
+    #[test]
+    fn test_retryable_failure_is_requeued_until_dead_letter() {
+        let queue = test_queue().with_max_attempts(2);
+        let job = queue.enqueue(JobKind::Review, &payload("page-1")).unwrap();
+        let error = AppError::notion_api(StatusCode::SERVICE_UNAVAILABLE, "");
+
+        let first = queue.claim_next().unwrap().unwrap();
+        assert_eq!(queue.mark_failed(&first, &error).unwrap(), JobState::Queued);
+        // run_after が未来なので、すぐには取り出されない
+        assert!(queue.claim_next().unwrap().is_none());
+
+        queue
+            .store
+            .connection()
+            .execute("UPDATE jobs SET run_after = ?1", [Utc::now()])
+            .unwrap();
+        let second = queue.claim_next().unwrap().unwrap();
+        assert_eq!(second.attempts, 2);
+        assert_eq!(
+            queue.mark_failed(&second, &error).unwrap(),
+            JobState::Failed
+        );
+
+        let stored = queue.get(job.id).unwrap().unwrap();
+        assert_eq!(stored.state, JobState::Failed);
+        assert!(stored.last_error.unwrap().contains("503"));
+    }
+
+    #[test]
+    fn test_permanent_failure_is_not_retried() {
+        let queue = test_queue();
+        queue.enqueue(JobKind::Diary, &payload("page-1")).unwrap();
+        let job = queue.claim_next().unwrap().unwrap();
+        let error = AppError::notion_api(StatusCode::NOT_FOUND, "");
+        assert_eq!(queue.mark_failed(&job, &error).unwrap(), JobState::Failed);
+    }
+
+    #[test]
+    fn test_recover_interrupted_jobs() {
+        let queue = test_queue();
+        let job = queue
+            .enqueue(JobKind::WeeklyReport, &payload("page-1"))
+            .unwrap();
+        queue.claim_next().unwrap().unwrap();
+
+        assert_eq!(queue.recover_interrupted().unwrap(), 1);
+        let recovered = queue.claim_next().unwrap().unwrap();
+        assert_eq!(recovered.id, job.id);
+        assert_eq!(recovered.attempts, 2);
+    }
+}
@@ -0,0 +1,51 @@
+use axum::{
+    extract::{Path, Query, State},
+    Json,
+};
+use reqwest::StatusCode;
+use serde::Deserialize;
+use serde_json::{json, Value};
+
+use super::{Job, DEFAULT_LIST_LIMIT};
+use crate::router::AppState;
+
+#[derive(Debug, Deserialize)]
+pub struct JobListQuery {
+    pub page_id: Option<String>,
+    pub limit: Option<u32>,
+}
+
+pub async fn get_job(
+    State(state): State<AppState>,
+    Path(id): Path<i64>,
+) -> Result<Json<Job>, (StatusCode, Json<Value>)> {
+    match state.job_queue.get(id) {
+        Ok(Some(job)) => Ok(Json(job)),
+        Ok(None) => Err((
+            StatusCode::NOT_FOUND,
+            Json(json!({ "error": format!("job {} not found", id) })),
+        )),
+        Err(e) => Err((
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(json!({ "error": e.to_string() })),
+        )),
+    }
+}
+
+pub async fn list_jobs(
+    State(state): State<AppState>,
+    Query(query): Query<JobListQuery>,
+) -> Result<Json<Vec<Job>>, (StatusCode, Json<Value>)> {
+    let limit = query.limit.unwrap_or(DEFAULT_LIST_LIMIT);
+    let jobs = match &query.page_id {
+        Some(page_id) => state.job_queue.list_by_page(page_id, limit),
+        None => state.job_queue.list_recent(limit),
+    };
+
+    jobs.map(Json).map_err(|e| {
+        (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(json!({ "error": e.to_string() })),
+        )
+    })
+}
@@ -0,0 +1,141 @@
+use std::time::Duration;
+
+use chrono::{DateTime, TimeDelta, Utc};
+use chrono_tz::Tz;
+
+use super::{Job, JobQueue};
+use crate::{
+    config::{ScheduleConfig, SchedulerConfig},
+    error::AppResult,
+    period::ReportPeriodRequest,
+    types::{NotionPageRef, NotionWebhookPayload},
+};
+
+// 予定時刻を過ぎていないか確認する間隔
+const TICK_INTERVAL: Duration = Duration::from_secs(30);
+
+/// 設定された cron 式に従って自動化のジョブを積むタスクを起動する
+pub fn spawn_scheduler(job_queue: JobQueue, config: SchedulerConfig, timezone: Tz) {
+    if config.schedules.is_empty() {
+        return;
+    }
+    for schedule in &config.schedules {
+        println!(
+            "Scheduled {} ({}) at \"{}\" {}",
+            schedule.name,
+            schedule.automation.as_str(),
+            schedule.cron.pattern,
+            timezone
+        );
+    }
+    tokio::spawn(async move { run_scheduler(job_queue, config, timezone).await });
+}
+
+async fn run_scheduler(job_queue: JobQueue, config: SchedulerConfig, timezone: Tz) {
+    loop {
+        for schedule in &config.schedules {
+            match enqueue_if_due(&job_queue, schedule, timezone, Utc::now()) {
+                Ok(Some(job)) => println!("Enqueued scheduled {} job {}", schedule.name, job.id),
+                Ok(None) => {}
+                Err(e) => println!("Failed to enqueue scheduled {}: {}", schedule.name, e),
+            }
+        }
+        tokio::time::sleep(TICK_INTERVAL).await;
+    }
+}
+
+// 直近の予定時刻が前回の実行より後ならジョブを積む。
+// 停止中に複数回分を過ぎていても、実行するのは 1 回だけ。
+// 初めて見る予定は、起動直後に予定時刻を過ぎた分 (1 tick 以内) だけを実行する
+fn enqueue_if_due(
+    job_queue: &JobQueue,
+    schedule: &ScheduleConfig,
+    timezone: Tz,
+    now: DateTime<Utc>,
+) -> AppResult<Option<Job>> {
+    let Ok(due_at) = schedule
+        .cron
+        .find_previous_occurrence(&now.with_timezone(&timezone), true)
+    else {
+        return Ok(None);
+    };
+
+    let payload = NotionWebhookPayload {
+        id: None,
+        source: None,
+        data: NotionPageRef {
+            id: schedule.page_id.clone(),
+        },
+        period: schedule.period.map(|kind| ReportPeriodRequest {
+            kind: Some(kind),
+            ..Default::default()
+        }),
+    };
+    let due_at = due_at.with_timezone(&Utc);
+    let run_first = now - due_at <= TimeDelta::from_std(TICK_INTERVAL).unwrap_or_default();
+    job_queue.enqueue_scheduled(
+        &schedule.name,
+        due_at,
+        run_first,
+        schedule.automation,
+        &payload,
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{jobs::JobKind, store::Store};
+    use chrono::TimeZone;
+
+    fn tokyo(day: u32, hour: u32, minute: u32) -> DateTime<Utc> {
+        chrono_tz::Asia::Tokyo
+            .with_ymd_and_hms(2024, 1, day, hour, minute, 0)
+            .unwrap()
+            .with_timezone(&Utc)
+    }
+
+    #[test]
+    fn test_enqueue_if_due_runs_each_occurrence_once() {
+        let job_queue = JobQueue::new(Store::open_in_memory().unwrap());
+        let schedule = ScheduleConfig {
+            name: "weekly-report".to_string(),
+            cron: "0 9 * * MON".parse().unwrap(),
+            automation: JobKind::WeeklyReport,
+            page_id: "report-page".to_string(),
+            period: None,
+        };
+        let run = |now| enqueue_if_due(&job_queue, &schedule, chrono_tz::Asia::Tokyo, now).unwrap();
+
+        // 初回は記録だけで、直前の月曜 9:00 はさかのぼらない
+        assert!(run(tokyo(8, 10, 0)).is_none());
+        assert!(run(tokyo(15, 8, 59)).is_none());
+
+        let job = run(tokyo(15, 9, 0)).unwrap();
+        assert_eq!(job.kind, JobKind::WeeklyReport);
+        assert_eq!(job.payload["data"]["id"], "report-page");
+        assert!(run(tokyo(15, 9, 30)).is_none());
+
+        // 停止中に 2 回分過ぎていても 1 回だけ積む
+        assert!(run(tokyo(31, 12, 0)).is_some());
+        assert!(run(tokyo(31, 12, 30)).is_none());
+    }
+
+    #[test]
+    fn test_first_tick_runs_occurrence_just_past_due() {
+        let job_queue = JobQueue::new(Store::open_in_memory().unwrap());
+        let schedule = ScheduleConfig {
+            name: "monthly-report".to_string(),
+            cron: "0 9 1 * *".parse().unwrap(),
+            automation: JobKind::PeriodicReport,
+            page_id: "report-page".to_string(),
+            period: Some(crate::period::ReportPeriodKind::Month),
+        };
+        let run = |now| enqueue_if_due(&job_queue, &schedule, chrono_tz::Asia::Tokyo, now).unwrap();
+
+        // 予定時刻の直後に起動した場合は、その回を実行する
+        let job = run(tokyo(1, 9, 0) + TimeDelta::seconds(20)).unwrap();
+        assert_eq!(job.payload["period"]["kind"], "month");
+        assert!(run(tokyo(1, 9, 1)).is_none());
+    }
+}
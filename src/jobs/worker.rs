@@ -0,0 +1,78 @@
+use std::time::Duration;
+
+use super::{Job, JobKind, JobState};
+use crate::{
+    automation::{
+        diary::diary_automation_process, report::periodic_report_process,
+        review::review_automation_process, AutomationOutcome,
+    },
+    error::AppResult,
+    period::ReportPeriodKind,
+    router::AppState,
+    types::NotionWebhookPayload,
+};
+
+// 通知がなくても、リトライ待ちのジョブを拾うために定期的にキューを確認する
+const POLL_INTERVAL: Duration = Duration::from_secs(5);
+
+/// concurrency 個のワーカーを起動し、キューのジョブを並行して処理する
+pub fn spawn_workers(state: AppState, concurrency: usize) {
+    for worker_id in 0..concurrency.max(1) {
+        let state = state.clone();
+        tokio::spawn(async move { run_worker(worker_id, state).await });
+    }
+}
+
+async fn run_worker(worker_id: usize, state: AppState) {
+    loop {
+        match state.job_queue.claim_next() {
+            Ok(Some(job)) => run_job(worker_id, &state, job).await,
+            Ok(None) => state.job_queue.wait_for_job(POLL_INTERVAL).await,
+            Err(e) => {
+                println!("Worker {} failed to claim job: {}", worker_id, e);
+                tokio::time::sleep(POLL_INTERVAL).await;
+            }
+        }
+    }
+}
+
+async fn run_job(worker_id: usize, state: &AppState, job: Job) {
+    println!(
+        "Worker {} started job {} ({}, attempt {}/{})",
+        worker_id,
+        job.id,
+        job.kind.as_str(),
+        job.attempts,
+        job.max_attempts
+    );
+
+    let result = match execute_job(state, &job).await {
+        Ok(outcome) => state
+            .job_queue
+            .mark_succeeded(job.id, &outcome)
+            .map(|_| JobState::Succeeded),
+        Err(e) => {
+            println!("Job {} failed: {}", job.id, e);
+            state.job_queue.mark_failed(&job, &e)
+        }
+    };
+
+    match result {
+        Ok(JobState::Succeeded) => println!("Job {} completed successfully", job.id),
+        Ok(JobState::Queued) => println!("Job {} will be retried", job.id),
+        Ok(state) => println!("Job {} moved to {}", job.id, state.as_str()),
+        Err(e) => println!("Failed to record result of job {}: {}", job.id, e),
+    }
+}
+
+async fn execute_job(state: &AppState, job: &Job) -> AppResult<AutomationOutcome> {
+    let payload: NotionWebhookPayload = serde_json::from_value(job.payload.clone())?;
+    match job.kind {
+        JobKind::Diary => diary_automation_process(state, payload).await,
+        JobKind::Review => review_automation_process(state, payload).await,
+        // payload で期間を指定しない限り、週報は直近の 1 週間をまとめる
+        JobKind::WeeklyReport | JobKind::PeriodicReport => {
+            periodic_report_process(state, payload, ReportPeriodKind::Week).await
+        }
+    }
+}
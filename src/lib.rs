@@ -1,5 +1,14 @@
 pub mod api;
 pub mod automation;
+pub mod config;
+pub mod error;
+pub mod jobs;
+pub mod llm;
+pub mod period;
+pub mod retry;
 pub mod router;
+pub mod schema;
 pub mod service;
+pub mod snapshot;
+pub mod store;
 pub mod types;
@@ -0,0 +1,330 @@
+use std::{borrow::Cow, sync::Arc};
+
+use async_trait::async_trait;
+
+use crate::{
+    config::{AutomationConfig, Config, LlmProviderKind, OutputFormat},
+    error::AppResult,
+    service::{GeminiService, OllamaService, OpenAiCompatibleService},
+    types::{markdown_to_blocks, NotionBlock, MAX_RICH_TEXT_LENGTH},
+};
+use repair::repair_blocks;
+
+pub mod gemini;
+pub mod ollama;
+pub mod openai;
+pub mod repair;
+pub mod summarize;
+
+// Markdown モードでは、JSON 形式を指示する組み込みプロンプトより後ろに置いて出力形式を上書きする
+const MARKDOWN_OUTPUT_INSTRUCTION: &str = "\n\n# 出力形式\n\
+上記で JSON 形式を指定している場合も、それには従わず Markdown で出力してください。\
+見出し (#, ##, ###)、箇条書き、番号付きリスト、チェックボックス (- [ ])、引用、コードブロック、区切り線、\
+太字・斜体・インラインコード・リンクが使えます。前置きや説明は不要です。";
+
+// JSON モードで最上位がオブジェクトに限られるプロバイダでは、配列を {"blocks": [...]} で受け取る
+pub(crate) const JSON_OBJECT_INSTRUCTION: &str = "\n\n\
+    ただし、出力は JSON 配列をそのまま返さず、{\"blocks\": [...]} の形の JSON オブジェクトにして、\
+    ブロックの配列を \"blocks\" に入れてください。";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LlmRole {
+    User,
+    Assistant,
+}
+
+#[derive(Debug, Clone)]
+pub struct LlmMessage {
+    pub role: LlmRole,
+    pub content: String,
+}
+
+impl LlmMessage {
+    pub fn user(content: impl Into<String>) -> Self {
+        Self {
+            role: LlmRole::User,
+            content: content.into(),
+        }
+    }
+
+    pub fn assistant(content: impl Into<String>) -> Self {
+        Self {
+            role: LlmRole::Assistant,
+            content: content.into(),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct GenerationParams {
+    pub model: String,
+    pub temperature: f32,
+}
+
+/// プロバイダに依存しない生成リクエスト
+#[derive(Debug, Clone)]
+pub struct LlmRequest {
+    pub system_prompt: String,
+    pub messages: Vec<LlmMessage>,
+    pub params: GenerationParams,
+    // JSON だけを返すようにプロバイダ側で制約する
+    pub json_mode: bool,
+    pub output_format: OutputFormat,
+    // 対応するプロバイダでは、出力をこの形に制約する
+    pub response_schema: Option<ResponseSchema>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ResponseSchema {
+    // Notion ブロックの配列
+    NotionBlocks,
+}
+
+impl LlmRequest {
+    /// 自動化の設定 (プロンプト・モデル・温度) でリクエストを組み立てる
+    pub fn new(automation: &AutomationConfig, messages: Vec<LlmMessage>) -> Self {
+        Self {
+            system_prompt: match automation.output {
+                OutputFormat::Json => automation.system_prompt.clone(),
+                OutputFormat::Markdown => {
+                    format!(
+                        "{}{}",
+                        automation.system_prompt, MARKDOWN_OUTPUT_INSTRUCTION
+                    )
+                }
+            },
+            messages,
+            params: GenerationParams {
+                model: automation.model.clone(),
+                temperature: automation.temperature,
+            },
+            json_mode: automation.output == OutputFormat::Json,
+            output_format: automation.output,
+            response_schema: match automation.output {
+                OutputFormat::Json => Some(ResponseSchema::NotionBlocks),
+                OutputFormat::Markdown => None,
+            },
+        }
+    }
+}
+
+impl LlmRequest {
+    /// JSON モードで最上位に配列を返せないプロバイダ向けに、{"blocks": [...]} で包むよう指示を足す
+    pub(crate) fn system_prompt_for_json_object(&self) -> Cow<'_, str> {
+        if self.json_mode {
+            Cow::Owned(format!("{}{}", self.system_prompt, JSON_OBJECT_INSTRUCTION))
+        } else {
+            Cow::Borrowed(&self.system_prompt)
+        }
+    }
+}
+
+#[async_trait]
+pub trait LlmProvider: Send + Sync {
+    fn name(&self) -> &'static str;
+
+    /// 生成されたテキストをそのまま返す
+    async fn generate(&self, request: &LlmRequest) -> AppResult<String>;
+}
+
+/// 設定された各プロバイダ。自動化ごとに provider で選ぶ
+#[derive(Clone)]
+pub struct LlmProviders {
+    gemini: Arc<dyn LlmProvider>,
+    openai: Arc<dyn LlmProvider>,
+    ollama: Arc<dyn LlmProvider>,
+}
+
+impl LlmProviders {
+    pub fn new(config: &Config) -> AppResult<Self> {
+        Ok(Self {
+            gemini: Arc::new(GeminiService::new(config)?),
+            openai: Arc::new(OpenAiCompatibleService::new(config)?),
+            ollama: Arc::new(OllamaService::new(config)?),
+        })
+    }
+
+    pub fn get(&self, kind: LlmProviderKind) -> Arc<dyn LlmProvider> {
+        match kind {
+            LlmProviderKind::Gemini => self.gemini.clone(),
+            LlmProviderKind::OpenAi => self.openai.clone(),
+            LlmProviderKind::Ollama => self.ollama.clone(),
+        }
+    }
+}
+
+/// ブロックを生成する。読めない出力は修復を試み、それでも駄目なら
+/// 解析エラーを添えて 1 度だけ作り直させる
+pub async fn generate_notion_blocks(
+    provider: &dyn LlmProvider,
+    request: &LlmRequest,
+) -> AppResult<Vec<NotionBlock>> {
+    let generated_content_str = provider.generate(request).await?;
+    println!("Generated Content String: {:?}", generated_content_str);
+
+    if request.output_format == OutputFormat::Markdown {
+        let blocks = markdown_to_blocks(unwrap_markdown_fence(&generated_content_str));
+        if blocks.is_empty() {
+            return Ok(fallback_blocks(&generated_content_str));
+        }
+        return Ok(blocks);
+    }
+
+    let error = match repair_blocks(&generated_content_str) {
+        Ok(blocks) => return Ok(blocks),
+        Err(error) => error,
+    };
+    println!(
+        "{} output is not valid Notion blocks ({}), asking for a correction",
+        provider.name(),
+        error
+    );
+
+    let mut retry_request = request.clone();
+    retry_request
+        .messages
+        .push(LlmMessage::assistant(generated_content_str));
+    retry_request.messages.push(LlmMessage::user(format!(
+        "直前の出力は Notion ブロックの JSON 配列として解釈できませんでした: {}\n\
+         説明やコードフェンスを付けず、修正した JSON 配列だけを出力してください。",
+        error
+    )));
+    let corrected_content_str = provider.generate(&retry_request).await?;
+    println!("Corrected Content String: {:?}", corrected_content_str);
+
+    match repair_blocks(&corrected_content_str) {
+        Ok(blocks) => Ok(blocks),
+        Err(error) => {
+            println!("Corrected output is still invalid: {}", error);
+            Ok(fallback_blocks(&corrected_content_str))
+        }
+    }
+}
+
+// 全体を ```markdown で囲んで返された場合は中身だけを使う
+fn unwrap_markdown_fence(text: &str) -> &str {
+    let trimmed = text.trim();
+    ["```markdown\n", "```md\n"]
+        .iter()
+        .find_map(|fence| trimmed.strip_prefix(fence))
+        .and_then(|body| body.trim_end().strip_suffix("```"))
+        .unwrap_or(trimmed)
+}
+
+// 生成結果を失わないよう、失敗の見出しに続けて生のテキストを残す
+fn fallback_blocks(raw_text: &str) -> Vec<NotionBlock> {
+    let chars: Vec<char> = raw_text.chars().collect();
+    std::iter::once(NotionBlock::heading_3("AIレスポンス生成に失敗しました"))
+        .chain(
+            chars
+                .chunks(MAX_RICH_TEXT_LENGTH)
+                .map(|chunk| NotionBlock::paragraph(&chunk.iter().collect::<String>())),
+        )
+        .collect()
+}
+
+// テスト用に、ローカルで OpenAI 互換や Ollama の代わりになるサーバーを立てる
+#[cfg(test)]
+pub(crate) async fn serve_stand_in(router: axum::Router) -> String {
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let address = listener.local_addr().unwrap();
+    tokio::spawn(async move { axum::serve(listener, router).await.unwrap() });
+    format!("http://{}", address)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{config::test_config, types::ExtractText};
+    use std::sync::Mutex;
+
+    // 用意した応答を順に返し、受け取ったリクエストを記録する
+    struct ScriptedProvider {
+        responses: Mutex<Vec<&'static str>>,
+        requests: Mutex<Vec<LlmRequest>>,
+    }
+
+    impl ScriptedProvider {
+        fn new(responses: &[&'static str]) -> Self {
+            Self {
+                responses: Mutex::new(responses.iter().rev().copied().collect()),
+                requests: Mutex::new(Vec::new()),
+            }
+        }
+    }
+
+    #[async_trait]
+    impl LlmProvider for ScriptedProvider {
+        fn name(&self) -> &'static str {
+            "Scripted"
+        }
+
+        async fn generate(&self, request: &LlmRequest) -> AppResult<String> {
+            self.requests.lock().unwrap().push(request.clone());
+            Ok(self.responses.lock().unwrap().pop().unwrap().to_string())
+        }
+    }
+
+    fn request() -> LlmRequest {
+        LlmRequest::new(
+            &test_config().automations.diary,
+            vec![LlmMessage::user("日記")],
+        )
+    }
+
+    #[tokio::test]
+    async fn test_generate_notion_blocks_asks_for_correction_once() {
+        let provider = ScriptedProvider::new(&[
+            "すみません、JSON を忘れました",
+            r#"[{"type": "divider", "divider": {}}]"#,
+        ]);
+        let blocks = generate_notion_blocks(&provider, &request()).await.unwrap();
+        assert_eq!(blocks.len(), 1);
+
+        let requests = provider.requests.lock().unwrap();
+        assert_eq!(requests.len(), 2);
+        let retry = &requests[1].messages;
+        assert_eq!(retry.len(), 3);
+        assert_eq!(retry[1].role, LlmRole::Assistant);
+        assert_eq!(retry[1].content, "すみません、JSON を忘れました");
+        assert!(
+            retry[2].content.contains("expected"),
+            "{}",
+            retry[2].content
+        );
+    }
+
+    #[tokio::test]
+    async fn test_generate_notion_blocks_falls_back_to_raw_text() {
+        let provider = ScriptedProvider::new(&["not json", "まだ JSON ではありません"]);
+        let blocks = generate_notion_blocks(&provider, &request()).await.unwrap();
+        assert_eq!(blocks.len(), 2);
+        assert_eq!(
+            blocks[1].extract_text().as_deref(),
+            Some("まだ JSON ではありません")
+        );
+    }
+
+    #[tokio::test]
+    async fn test_generate_notion_blocks_converts_markdown() {
+        let mut automation = test_config().automations.review;
+        automation.output = OutputFormat::Markdown;
+        let request = LlmRequest::new(&automation, vec![LlmMessage::user("コード")]);
+        assert!(!request.json_mode);
+        assert!(request.response_schema.is_none());
+        assert!(request.system_prompt.ends_with(MARKDOWN_OUTPUT_INSTRUCTION));
+
+        let provider = ScriptedProvider::new(&["```markdown\n## レビュー\n- 良い点\n```"]);
+        let blocks = generate_notion_blocks(&provider, &request).await.unwrap();
+        assert_eq!(blocks.len(), 2);
+        assert_eq!(blocks[0].extract_text().as_deref(), Some("レビュー"));
+        assert_eq!(provider.requests.lock().unwrap().len(), 1);
+    }
+
+    #[test]
+    fn test_fallback_blocks_split_long_text() {
+        let blocks = fallback_blocks(&"あ".repeat(MAX_RICH_TEXT_LENGTH + 1));
+        assert_eq!(blocks.len(), 3);
+        assert_eq!(blocks[2].extract_text().as_deref(), Some("あ"));
+    }
+}
@@ -0,0 +1,185 @@
+use async_trait::async_trait;
+
+use super::{LlmProvider, LlmRequest, LlmRole, ResponseSchema};
+use crate::{
+    error::{AppError, AppResult},
+    retry::send_with_retry,
+    service::GeminiService,
+    types::{
+        GeminiAPIChatContent, GeminiAPIModel, GeminiAPIPrompt, GeminiAPIResponse, GenerationConfig,
+        NotionBlock, Part, ResponseMimeType, Role,
+    },
+};
+
+#[async_trait]
+impl LlmProvider for GeminiService {
+    fn name(&self) -> &'static str {
+        "Gemini"
+    }
+
+    async fn generate(&self, request: &LlmRequest) -> AppResult<String> {
+        let model = GeminiAPIModel::from_name(&request.params.model);
+        let response = push_to_gemini_api(self, gen_gemini_prompt(request), &model).await?;
+        extract_generated_text(response)
+    }
+}
+
+fn gen_gemini_prompt(request: &LlmRequest) -> GeminiAPIPrompt {
+    let system_instruction = Some(GeminiAPIChatContent {
+        role: Some(Role::User),
+        parts: vec![Part {
+            text: request.system_prompt.clone(),
+        }],
+    });
+
+    let contents = request
+        .messages
+        .iter()
+        .map(|message| GeminiAPIChatContent {
+            role: Some(match message.role {
+                LlmRole::User => Role::User,
+                LlmRole::Assistant => Role::Model,
+            }),
+            parts: vec![Part {
+                text: message.content.clone(),
+            }],
+        })
+        .collect();
+
+    let generation_config = Some(GenerationConfig {
+        temperature: request.params.temperature,
+        response_mime_type: if request.json_mode {
+            ResponseMimeType::Json
+        } else {
+            ResponseMimeType::Text
+        },
+        response_schema: request
+            .response_schema
+            .filter(|_| request.json_mode)
+            .map(|schema| match schema {
+                ResponseSchema::NotionBlocks => NotionBlock::gemini_response_schema(),
+            }),
+    });
+
+    GeminiAPIPrompt {
+        contents,
+        system_instruction,
+        generation_config,
+    }
+}
+
+async fn push_to_gemini_api(
+    service: &GeminiService,
+    prompt: GeminiAPIPrompt,
+    model: &GeminiAPIModel,
+) -> AppResult<GeminiAPIResponse> {
+    let model = model.model_name();
+
+    let url = format!(
+        "https://generativelanguage.googleapis.com/v1beta/models/{}:generateContent?key={}",
+        model, service.api_key
+    );
+
+    let request = service.client.post(url).json(&prompt);
+    let response = send_with_retry(&service.retry_policy, None, request).await?;
+
+    let status = response.status();
+    let body_text = response.text().await?;
+    if !status.is_success() {
+        return Err(AppError::gemini_api(status, &body_text));
+    }
+
+    Ok(serde_json::from_str(&body_text)?)
+}
+
+// 生成テキストを取り出す。ブロックされた場合は理由をエラーとして返す
+fn extract_generated_text(response: GeminiAPIResponse) -> AppResult<String> {
+    if let Some(reason) = response
+        .prompt_feedback
+        .and_then(|feedback| feedback.block_reason)
+    {
+        return Err(AppError::GeminiBlocked { reason });
+    }
+
+    let candidate = response
+        .candidates
+        .into_iter()
+        .next()
+        .ok_or(AppError::GeminiEmptyResponse)?;
+
+    match candidate
+        .content
+        .and_then(|content| content.parts.into_iter().next())
+    {
+        Some(part) => Ok(part.text),
+        None => match candidate.finish_reason {
+            Some(reason) if reason != "STOP" => Err(AppError::GeminiBlocked { reason }),
+            _ => Err(AppError::GeminiEmptyResponse),
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{config::test_config, llm::LlmMessage};
+
+    #[test]
+    fn test_gen_gemini_prompt() {
+        let mut automation = test_config().automations.diary;
+        automation.temperature = 0.5;
+        let request = LlmRequest::new(
+            &automation,
+            vec![
+                LlmMessage::user("今日は散歩した"),
+                LlmMessage::assistant("[]"),
+            ],
+        );
+
+        let prompt = serde_json::to_value(gen_gemini_prompt(&request)).unwrap();
+        assert_eq!(prompt["contents"][0]["role"], "user");
+        assert_eq!(prompt["contents"][0]["parts"][0]["text"], "今日は散歩した");
+        assert_eq!(prompt["contents"][1]["role"], "model");
+        assert_eq!(prompt["generationConfig"]["temperature"], 0.5);
+        assert_eq!(
+            prompt["generationConfig"]["responseMimeType"],
+            "application/json"
+        );
+        assert_eq!(
+            prompt["generationConfig"]["responseSchema"]["type"],
+            "ARRAY"
+        );
+    }
+
+    #[test]
+    fn test_extract_generated_text() {
+        let response: GeminiAPIResponse = serde_json::from_str(
+            r#"{"candidates":[{"content":{"role":"model","parts":[{"text":"[]"}]},"finishReason":"STOP"}]}"#,
+        )
+        .unwrap();
+        assert_eq!(extract_generated_text(response).unwrap(), "[]");
+    }
+
+    #[test]
+    fn test_extract_generated_text_blocked() {
+        let prompt_blocked: GeminiAPIResponse =
+            serde_json::from_str(r#"{"promptFeedback":{"blockReason":"SAFETY"}}"#).unwrap();
+        assert!(matches!(
+            extract_generated_text(prompt_blocked),
+            Err(AppError::GeminiBlocked { reason }) if reason == "SAFETY"
+        ));
+
+        let candidate_blocked: GeminiAPIResponse =
+            serde_json::from_str(r#"{"candidates":[{"finishReason":"SAFETY"}]}"#).unwrap();
+        assert!(matches!(
+            extract_generated_text(candidate_blocked),
+            Err(AppError::GeminiBlocked { .. })
+        ));
+
+        let empty: GeminiAPIResponse = serde_json::from_str(r#"{"candidates":[]}"#).unwrap();
+        assert!(matches!(
+            extract_generated_text(empty),
+            Err(AppError::GeminiEmptyResponse)
+        ));
+    }
+}
@@ -0,0 +1,154 @@
+use std::borrow::Cow;
+
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+
+use super::{LlmProvider, LlmRequest, LlmRole};
+use crate::{
+    error::{AppError, AppResult},
+    retry::send_with_retry,
+    service::OllamaService,
+};
+
+const PROVIDER_NAME: &str = "Ollama";
+
+#[derive(Debug, Serialize)]
+struct OllamaChatRequest<'a> {
+    model: &'a str,
+    messages: Vec<OllamaMessage<'a>>,
+    // ストリーミングせず、1 つのレスポンスで受け取る
+    stream: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    format: Option<&'static str>,
+    options: OllamaOptions,
+}
+
+#[derive(Debug, Serialize)]
+struct OllamaMessage<'a> {
+    role: &'static str,
+    content: Cow<'a, str>,
+}
+
+#[derive(Debug, Serialize)]
+struct OllamaOptions {
+    temperature: f32,
+}
+
+#[derive(Debug, Deserialize)]
+struct OllamaChatResponse {
+    message: Option<OllamaResponseMessage>,
+}
+
+#[derive(Debug, Deserialize)]
+struct OllamaResponseMessage {
+    content: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct OllamaErrorResponse {
+    error: String,
+}
+
+#[async_trait]
+impl LlmProvider for OllamaService {
+    fn name(&self) -> &'static str {
+        PROVIDER_NAME
+    }
+
+    async fn generate(&self, request: &LlmRequest) -> AppResult<String> {
+        let url = format!("{}/api/chat", self.base_url);
+        let http_request = self.client.post(url).json(&gen_ollama_request(request));
+        let response = send_with_retry(&self.retry_policy, None, http_request).await?;
+
+        let status = response.status();
+        let body_text = response.text().await?;
+        if !status.is_success() {
+            let message = serde_json::from_str::<OllamaErrorResponse>(&body_text)
+                .map(|response| response.error)
+                .unwrap_or(body_text);
+            return Err(AppError::LlmApi {
+                provider: PROVIDER_NAME,
+                status,
+                message,
+            });
+        }
+
+        let response: OllamaChatResponse = serde_json::from_str(&body_text)?;
+        response
+            .message
+            .map(|message| message.content)
+            .filter(|content| !content.is_empty())
+            .ok_or(AppError::LlmEmptyResponse {
+                provider: PROVIDER_NAME,
+            })
+    }
+}
+
+fn gen_ollama_request(request: &LlmRequest) -> OllamaChatRequest<'_> {
+    let system_message = OllamaMessage {
+        role: "system",
+        // format: "json" は最上位がオブジェクトになる
+        content: request.system_prompt_for_json_object(),
+    };
+    let messages = std::iter::once(system_message)
+        .chain(request.messages.iter().map(|message| OllamaMessage {
+            role: match message.role {
+                LlmRole::User => "user",
+                LlmRole::Assistant => "assistant",
+            },
+            content: Cow::Borrowed(&message.content),
+        }))
+        .collect();
+
+    OllamaChatRequest {
+        model: &request.params.model,
+        messages,
+        stream: false,
+        format: request.json_mode.then_some("json"),
+        options: OllamaOptions {
+            temperature: request.params.temperature,
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        config::test_config,
+        llm::{serve_stand_in, LlmMessage, JSON_OBJECT_INSTRUCTION},
+    };
+    use axum::{routing::post, Json, Router};
+    use serde_json::{json, Value};
+
+    #[tokio::test]
+    async fn test_generate_against_stand_in() {
+        let router = Router::new().route(
+            "/api/chat",
+            post(|Json(body): Json<Value>| async move {
+                assert_eq!(body["model"], "llama3.1");
+                assert_eq!(body["stream"], false);
+                assert_eq!(body["format"], "json");
+                assert_eq!(body["messages"][0]["role"], "system");
+                assert!(body["messages"][0]["content"]
+                    .as_str()
+                    .unwrap()
+                    .ends_with(JSON_OBJECT_INSTRUCTION));
+                assert_eq!(body["messages"][1]["role"], "user");
+                Json(json!({
+                    "model": "llama3.1",
+                    "message": { "role": "assistant", "content": "{\"blocks\":[]}" },
+                    "done": true
+                }))
+            }),
+        );
+        let mut config = test_config();
+        config.ollama.base_url = serve_stand_in(router).await;
+        let service = OllamaService::new(&config).unwrap();
+
+        let mut automation = config.automations.diary;
+        automation.model = "llama3.1".to_string();
+        let request = LlmRequest::new(&automation, vec![LlmMessage::user("今日は散歩した")]);
+        assert_eq!(service.generate(&request).await.unwrap(), "{\"blocks\":[]}");
+    }
+}
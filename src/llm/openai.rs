@@ -0,0 +1,205 @@
+use std::borrow::Cow;
+
+use async_trait::async_trait;
+use reqwest::header::AUTHORIZATION;
+use serde::{Deserialize, Serialize};
+
+use super::{LlmProvider, LlmRequest, LlmRole};
+use crate::{
+    error::{AppError, AppResult},
+    retry::send_with_retry,
+    service::OpenAiCompatibleService,
+};
+
+const PROVIDER_NAME: &str = "OpenAI-compatible";
+
+#[derive(Debug, Serialize)]
+struct ChatCompletionRequest<'a> {
+    model: &'a str,
+    messages: Vec<ChatMessage<'a>>,
+    temperature: f32,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    response_format: Option<ResponseFormat>,
+}
+
+#[derive(Debug, Serialize)]
+struct ChatMessage<'a> {
+    role: &'static str,
+    content: Cow<'a, str>,
+}
+
+#[derive(Debug, Serialize)]
+struct ResponseFormat {
+    #[serde(rename = "type")]
+    format_type: &'static str,
+}
+
+#[derive(Debug, Deserialize)]
+struct ChatCompletionResponse {
+    #[serde(default)]
+    choices: Vec<ChatCompletionChoice>,
+}
+
+#[derive(Debug, Deserialize)]
+struct ChatCompletionChoice {
+    message: ChatCompletionMessage,
+}
+
+#[derive(Debug, Deserialize)]
+struct ChatCompletionMessage {
+    content: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct ChatCompletionErrorResponse {
+    error: ChatCompletionErrorDetail,
+}
+
+#[derive(Debug, Deserialize)]
+struct ChatCompletionErrorDetail {
+    message: String,
+}
+
+#[async_trait]
+impl LlmProvider for OpenAiCompatibleService {
+    fn name(&self) -> &'static str {
+        PROVIDER_NAME
+    }
+
+    async fn generate(&self, request: &LlmRequest) -> AppResult<String> {
+        let url = format!("{}/chat/completions", self.base_url);
+        let mut http_request = self
+            .client
+            .post(url)
+            .json(&gen_chat_completion_request(request));
+        if let Some(api_key) = &self.api_key {
+            http_request = http_request.header(AUTHORIZATION, format!("Bearer {}", api_key));
+        }
+        let response = send_with_retry(&self.retry_policy, None, http_request).await?;
+
+        let status = response.status();
+        let body_text = response.text().await?;
+        if !status.is_success() {
+            let message = serde_json::from_str::<ChatCompletionErrorResponse>(&body_text)
+                .map(|response| response.error.message)
+                .unwrap_or(body_text);
+            return Err(AppError::LlmApi {
+                provider: PROVIDER_NAME,
+                status,
+                message,
+            });
+        }
+
+        let response: ChatCompletionResponse = serde_json::from_str(&body_text)?;
+        response
+            .choices
+            .into_iter()
+            .next()
+            .and_then(|choice| choice.message.content)
+            .ok_or(AppError::LlmEmptyResponse {
+                provider: PROVIDER_NAME,
+            })
+    }
+}
+
+fn gen_chat_completion_request(request: &LlmRequest) -> ChatCompletionRequest<'_> {
+    let system_message = ChatMessage {
+        role: "system",
+        // json_object は最上位がオブジェクトでなければならない
+        content: request.system_prompt_for_json_object(),
+    };
+    let messages = std::iter::once(system_message)
+        .chain(request.messages.iter().map(|message| ChatMessage {
+            role: match message.role {
+                LlmRole::User => "user",
+                LlmRole::Assistant => "assistant",
+            },
+            content: Cow::Borrowed(&message.content),
+        }))
+        .collect();
+
+    ChatCompletionRequest {
+        model: &request.params.model,
+        messages,
+        temperature: request.params.temperature,
+        response_format: request.json_mode.then_some(ResponseFormat {
+            format_type: "json_object",
+        }),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        config::{test_config, OutputFormat},
+        llm::{serve_stand_in, LlmMessage, JSON_OBJECT_INSTRUCTION},
+    };
+    use axum::{routing::post, Json, Router};
+    use serde_json::{json, Value};
+
+    #[tokio::test]
+    async fn test_generate_against_stand_in() {
+        let router = Router::new().route(
+            "/v1/chat/completions",
+            post(|Json(body): Json<Value>| async move {
+                assert_eq!(body["model"], "local-model");
+                assert_eq!(body["messages"][0]["role"], "system");
+                assert!(body["messages"][0]["content"]
+                    .as_str()
+                    .unwrap()
+                    .ends_with(JSON_OBJECT_INSTRUCTION));
+                assert_eq!(body["messages"][1]["content"], "今日は散歩した");
+                assert_eq!(body["response_format"]["type"], "json_object");
+                Json(json!({
+                    "choices": [{
+                        "index": 0,
+                        "message": { "role": "assistant", "content": "{\"blocks\":[]}" },
+                        "finish_reason": "stop"
+                    }]
+                }))
+            }),
+        );
+        let mut config = test_config();
+        config.openai.base_url = format!("{}/v1/", serve_stand_in(router).await);
+        let service = OpenAiCompatibleService::new(&config).unwrap();
+
+        let mut automation = config.automations.diary;
+        automation.model = "local-model".to_string();
+        let request = LlmRequest::new(&automation, vec![LlmMessage::user("今日は散歩した")]);
+        assert_eq!(service.generate(&request).await.unwrap(), "{\"blocks\":[]}");
+    }
+
+    #[tokio::test]
+    async fn test_generate_reports_api_error() {
+        let router = Router::new().route(
+            "/chat/completions",
+            post(|| async {
+                (
+                    reqwest::StatusCode::BAD_REQUEST,
+                    Json(json!({ "error": { "message": "model not found" } })),
+                )
+            }),
+        );
+        let mut config = test_config();
+        config.openai.base_url = serve_stand_in(router).await;
+        let service = OpenAiCompatibleService::new(&config).unwrap();
+
+        let request = LlmRequest::new(&config.automations.diary, vec![]);
+        let error = service.generate(&request).await.unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "OpenAI-compatible API error: status 400 Bad Request: model not found"
+        );
+    }
+
+    #[test]
+    fn test_blocks_wrapper_is_only_requested_in_json_mode() {
+        let mut automation = test_config().automations.diary;
+        automation.output = OutputFormat::Markdown;
+        let request = LlmRequest::new(&automation, vec![]);
+        let body = serde_json::to_value(gen_chat_completion_request(&request)).unwrap();
+        assert_eq!(body["messages"][0]["content"], request.system_prompt);
+        assert!(body["response_format"].is_null());
+    }
+}
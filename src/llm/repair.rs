@@ -0,0 +1,235 @@
+use serde_json::Value;
+
+use crate::types::{NotionBlock, RichTextColor};
+
+/// LLM の出力を Notion ブロックとして読めるように修復する。
+/// 個別に読めないブロックは捨て、1 つも残らなければ解析エラーを返す
+pub fn repair_blocks(text: &str) -> Result<Vec<NotionBlock>, String> {
+    let json = remove_trailing_commas(strip_code_fences(text));
+    let value: Value = serde_json::from_str(&json).map_err(|error| error.to_string())?;
+
+    let items = match value {
+        Value::Array(items) => items,
+        // JSON モードで配列を返せないプロバイダは {"blocks": [...]} のように包んで返すことがある
+        Value::Object(object) => match object.into_iter().find_map(|(_, value)| match value {
+            Value::Array(items) => Some(items),
+            _ => None,
+        }) {
+            Some(items) => items,
+            None => return Err("expected a JSON array of Notion blocks".to_string()),
+        },
+        _ => return Err("expected a JSON array of Notion blocks".to_string()),
+    };
+
+    let total = items.len();
+    let mut first_error = None;
+    let blocks: Vec<NotionBlock> = items
+        .into_iter()
+        .filter_map(|item| match parse_block(item) {
+            Ok(block) => Some(block),
+            Err(error) => {
+                first_error.get_or_insert(error);
+                None
+            }
+        })
+        .collect();
+
+    if let Some(error) = &first_error {
+        println!(
+            "Dropped {} invalid blocks from LLM output: {}",
+            total - blocks.len(),
+            error
+        );
+    }
+    match first_error {
+        Some(error) if blocks.is_empty() => Err(format!("no valid Notion blocks: {}", error)),
+        _ => Ok(blocks),
+    }
+}
+
+// ```json ... ``` で囲まれた出力から中身を取り出す
+fn strip_code_fences(text: &str) -> &str {
+    let trimmed = text.trim();
+    let Some(rest) = trimmed.strip_prefix("```") else {
+        return trimmed;
+    };
+    // 開始行の言語名 (json など) は読み飛ばす
+    let body = rest.split_once('\n').map_or("", |(_, body)| body);
+    body.trim_end().strip_suffix("```").unwrap_or(body).trim()
+}
+
+// 文字列の外にある `,]` `,}` の余分なカンマを取り除く
+fn remove_trailing_commas(json: &str) -> String {
+    let mut output = String::with_capacity(json.len());
+    let mut in_string = false;
+    let mut escaped = false;
+    // 直前のカンマと、その後に続く空白
+    let mut pending_comma: Option<String> = None;
+
+    for c in json.chars() {
+        if in_string {
+            output.push(c);
+            match c {
+                _ if escaped => escaped = false,
+                '\\' => escaped = true,
+                '"' => in_string = false,
+                _ => {}
+            }
+            continue;
+        }
+
+        if let Some(mut whitespace) = pending_comma.take() {
+            if c.is_whitespace() {
+                whitespace.push(c);
+                pending_comma = Some(whitespace);
+                continue;
+            }
+            if !matches!(c, ']' | '}') {
+                output.push(',');
+            }
+            output.push_str(&whitespace);
+        }
+
+        match c {
+            ',' => pending_comma = Some(String::new()),
+            '"' => {
+                in_string = true;
+                output.push(c);
+            }
+            _ => output.push(c),
+        }
+    }
+    if let Some(whitespace) = pending_comma {
+        output.push(',');
+        output.push_str(&whitespace);
+    }
+    output
+}
+
+// 子ブロックも個別に検証し、読めないものだけを取り除く
+pub(crate) fn parse_block(mut value: Value) -> Result<NotionBlock, String> {
+    let block_type = value
+        .get("type")
+        .and_then(Value::as_str)
+        .ok_or_else(|| "block without a type".to_string())?
+        .to_string();
+
+    if let Some(content) = value.get_mut(&block_type).and_then(Value::as_object_mut) {
+        if let Some(Value::Array(rich_text)) = content.get_mut("rich_text") {
+            rich_text.iter_mut().for_each(fix_annotations);
+        }
+        if let Some(children) = content.remove("children") {
+            let children: Vec<NotionBlock> = match children {
+                Value::Array(children) => children
+                    .into_iter()
+                    .filter_map(|child| parse_block(child).ok())
+                    .collect(),
+                _ => Vec::new(),
+            };
+            if !children.is_empty() {
+                let children = serde_json::to_value(children).map_err(|e| e.to_string())?;
+                content.insert("children".to_string(), children);
+            }
+        }
+    }
+
+    match serde_json::from_value(value) {
+        Ok(NotionBlock::Unsupported) => Err(format!("unsupported block type {}", block_type)),
+        Ok(block) => Ok(block),
+        Err(error) => Err(format!("invalid {} block: {}", block_type, error)),
+    }
+}
+
+// Notion はコード装飾と色を同時に受け付けないため、code のときは色を外す。
+// 読めない色も取り除く
+fn fix_annotations(rich_text: &mut Value) {
+    let Some(annotations) = rich_text
+        .get_mut("annotations")
+        .and_then(Value::as_object_mut)
+    else {
+        return;
+    };
+    let is_code = annotations.get("code").and_then(Value::as_bool) == Some(true);
+    let valid_color = annotations
+        .get("color")
+        .is_some_and(|color| serde_json::from_value::<RichTextColor>(color.clone()).is_ok());
+    if is_code || !valid_color {
+        annotations.remove("color");
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_strip_code_fences() {
+        assert_eq!(strip_code_fences("```json\n[]\n```"), "[]");
+        assert_eq!(strip_code_fences("  ```\n[1]\n```  "), "[1]");
+        assert_eq!(strip_code_fences("[]"), "[]");
+    }
+
+    #[test]
+    fn test_remove_trailing_commas() {
+        assert_eq!(
+            remove_trailing_commas(r#"[{"a": [1, 2,], "b": "x,]",},]"#),
+            r#"[{"a": [1, 2], "b": "x,]"}]"#
+        );
+        assert_eq!(remove_trailing_commas("[1,\n  2,\n]"), "[1,\n  2\n]");
+        assert_eq!(remove_trailing_commas(r#"["a\",]"]"#), r#"["a\",]"]"#);
+    }
+
+    #[test]
+    fn test_repair_blocks_drops_invalid_blocks() {
+        let text = r#"```json
+[
+  {"type": "heading_2", "heading_2": {"rich_text": [{"type": "text", "text": {"content": "振り返り"}}]}},
+  {"type": "paragraph", "paragraph": {"text": "rich_text がない"}},
+  {"type": "image", "image": {}},
+  {
+    "type": "bulleted_list_item",
+    "bulleted_list_item": {
+      "rich_text": [{"type": "text", "text": {"content": "親"}}],
+      "children": [
+        {"type": "paragraph", "paragraph": {}},
+        {"type": "paragraph", "paragraph": {"rich_text": [{"type": "text", "text": {"content": "子"}}]}},
+      ],
+    },
+  },
+]
+```"#;
+        let blocks = repair_blocks(text).unwrap();
+        assert_eq!(blocks.len(), 2);
+        assert_eq!(blocks[1].children().unwrap().len(), 1);
+    }
+
+    #[test]
+    fn test_repair_blocks_removes_color_from_code_annotations() {
+        let text = r#"[{"type": "paragraph", "paragraph": {"rich_text": [
+            {"type": "text", "text": {"content": "x"}, "annotations": {"code": true, "color": "red"}},
+            {"type": "text", "text": {"content": "y"}, "annotations": {"color": "rainbow"}},
+            {"type": "text", "text": {"content": "z"}, "annotations": {"color": "blue"}}
+        ]}}]"#;
+        let blocks = repair_blocks(text).unwrap();
+        let json = serde_json::to_value(&blocks[0]).unwrap();
+        let rich_text = &json["paragraph"]["rich_text"];
+        assert!(rich_text[0]["annotations"].get("color").is_none());
+        assert!(rich_text[1]["annotations"].get("color").is_none());
+        assert_eq!(rich_text[2]["annotations"]["color"], "blue");
+    }
+
+    #[test]
+    fn test_repair_blocks_reports_errors() {
+        assert!(repair_blocks("レビューを書きました").is_err());
+        assert!(repair_blocks(r#"[{"type": "image"}]"#)
+            .unwrap_err()
+            .starts_with("no valid Notion blocks"));
+        assert!(repair_blocks("[]").unwrap().is_empty());
+        assert_eq!(
+            repair_blocks(r#"{"blocks": [{"type": "divider", "divider": {}}]}"#)
+                .unwrap()
+                .len(),
+            1
+        );
+    }
+}
@@ -0,0 +1,245 @@
+use super::{GenerationParams, LlmMessage, LlmProvider, LlmRequest};
+use crate::{
+    config::{AutomationConfig, InputBudgetConfig, OutputFormat},
+    error::AppResult,
+};
+
+const CHUNK_SUMMARY_PROMPT: &str = include_str!("../prompts/chunk_summary.txt");
+
+// 要約しても収まらない場合に、要約の要約を繰り返す上限
+const MAX_REDUCE_ROUNDS: usize = 3;
+
+/// 日本語は 1 文字 ≒ 1 トークン、英数字は 4 文字 ≒ 1 トークンとして大まかに見積もる
+pub fn estimate_tokens(text: &str) -> usize {
+    let ascii = text.chars().filter(char::is_ascii).count();
+    let others = text.chars().count() - ascii;
+    others + ascii.div_ceil(4)
+}
+
+fn fits_in_budget(text: &str, budget: &InputBudgetConfig) -> bool {
+    budget
+        .max_chars
+        .is_none_or(|max_chars| text.chars().count() <= max_chars)
+        && budget
+            .max_tokens
+            .is_none_or(|max_tokens| estimate_tokens(text) <= max_tokens)
+}
+
+/// 入力が自動化の budget を超える場合、チャンクごとに要約してから連結する (map-reduce の map)。
+/// 要約しても超える場合は、要約をさらに要約する
+pub async fn reduce_to_budget(
+    provider: &dyn LlmProvider,
+    automation: &AutomationConfig,
+    text: String,
+) -> AppResult<String> {
+    let Some(budget) = &automation.budget else {
+        return Ok(text);
+    };
+
+    let mut text = text;
+    for round in 1..=MAX_REDUCE_ROUNDS {
+        if fits_in_budget(&text, budget) {
+            return Ok(text);
+        }
+        let chunks = split_into_chunks(&text, budget.chunk_chars);
+        println!(
+            "Input of {} chars exceeds the budget, summarizing {} chunks (round {})",
+            text.chars().count(),
+            chunks.len(),
+            round
+        );
+
+        let mut summaries = Vec::with_capacity(chunks.len());
+        for (index, chunk) in chunks.iter().enumerate() {
+            let summary = provider
+                .generate(&chunk_summary_request(automation, chunk))
+                .await?;
+            summaries.push(format!(
+                "\n--- 要約 {}/{} ---\n{}\n",
+                index + 1,
+                chunks.len(),
+                summary.trim()
+            ));
+        }
+        text = summaries.concat();
+    }
+
+    if !fits_in_budget(&text, budget) {
+        println!(
+            "Input still exceeds the budget after {} rounds, generating anyway",
+            MAX_REDUCE_ROUNDS
+        );
+    }
+    Ok(text)
+}
+
+// 要約は Notion ブロックではなく、プレーンな Markdown で返させる
+fn chunk_summary_request(automation: &AutomationConfig, chunk: &str) -> LlmRequest {
+    LlmRequest {
+        system_prompt: CHUNK_SUMMARY_PROMPT.to_string(),
+        messages: vec![LlmMessage::user(chunk)],
+        params: GenerationParams {
+            model: automation.model.clone(),
+            temperature: automation.temperature,
+        },
+        json_mode: false,
+        output_format: OutputFormat::Markdown,
+        response_schema: None,
+    }
+}
+
+/// "--- 見出し ---" で始まる記録の区切りを保ったまま、chunk_chars 文字以下のチャンクに分ける。
+/// 1 件で chunk_chars を超える記録は行の区切りで、1 行で超える場合は文字数で分ける
+pub fn split_into_chunks(text: &str, chunk_chars: usize) -> Vec<String> {
+    let chunk_chars = chunk_chars.max(1);
+    let mut chunks = vec![];
+    let mut current = String::new();
+    let mut current_chars = 0;
+
+    for piece in split_entries(text)
+        .into_iter()
+        .flat_map(|entry| split_long(entry, chunk_chars))
+    {
+        let piece_chars = piece.chars().count();
+        if current_chars + piece_chars > chunk_chars && !current.is_empty() {
+            chunks.push(std::mem::take(&mut current));
+            current_chars = 0;
+        }
+        current.push_str(&piece);
+        current_chars += piece_chars;
+    }
+    if !current.trim().is_empty() {
+        chunks.push(current);
+    }
+    chunks
+}
+
+// 記録の見出し行の直前で区切る
+fn split_entries(text: &str) -> Vec<&str> {
+    let mut entries = vec![];
+    let mut start = 0;
+    for (index, _) in text.match_indices("\n--- ") {
+        if index > start {
+            entries.push(&text[start..index]);
+            start = index;
+        }
+    }
+    entries.push(&text[start..]);
+    entries
+}
+
+fn split_long(entry: &str, chunk_chars: usize) -> Vec<String> {
+    if entry.chars().count() <= chunk_chars {
+        return vec![entry.to_string()];
+    }
+    let mut pieces = vec![];
+    let mut current = String::new();
+    let mut current_chars = 0;
+    for line in entry.split_inclusive('\n') {
+        let chars: Vec<char> = line.chars().collect();
+        for part in chars.chunks(chunk_chars) {
+            if current_chars + part.len() > chunk_chars && !current.is_empty() {
+                pieces.push(std::mem::take(&mut current));
+                current_chars = 0;
+            }
+            current.extend(part);
+            current_chars += part.len();
+        }
+    }
+    if !current.is_empty() {
+        pieces.push(current);
+    }
+    pieces
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::test_config;
+    use async_trait::async_trait;
+    use std::sync::Mutex;
+
+    // 受け取ったチャンクの文字数だけを返す
+    struct CountingProvider {
+        requests: Mutex<Vec<LlmRequest>>,
+    }
+
+    #[async_trait]
+    impl LlmProvider for CountingProvider {
+        fn name(&self) -> &'static str {
+            "Counting"
+        }
+
+        async fn generate(&self, request: &LlmRequest) -> AppResult<String> {
+            self.requests.lock().unwrap().push(request.clone());
+            Ok(format!(
+                "{} 文字",
+                request.messages[0].content.chars().count()
+            ))
+        }
+    }
+
+    fn entries(count: usize, body: &str) -> String {
+        (1..=count)
+            .map(|day| format!("\n--- Diary Entry (2024-01-{:02}) ---\n{}\n", day, body))
+            .collect()
+    }
+
+    #[test]
+    fn test_split_into_chunks_keeps_entries_together() {
+        let text = entries(4, &"あ".repeat(30));
+        let chunks = split_into_chunks(&text, 140);
+        assert_eq!(chunks.len(), 2);
+        assert_eq!(chunks.concat(), text);
+        assert!(chunks
+            .iter()
+            .all(|chunk| chunk.starts_with("\n--- Diary Entry")));
+
+        // 1 件でも長すぎる記録は分ける
+        let long = entries(1, &"い".repeat(250));
+        let chunks = split_into_chunks(&long, 100);
+        assert!(chunks.len() > 1);
+        assert!(chunks.iter().all(|chunk| chunk.chars().count() <= 100));
+        assert_eq!(chunks.concat(), long);
+    }
+
+    #[test]
+    fn test_estimate_tokens() {
+        assert_eq!(estimate_tokens("日記"), 2);
+        assert_eq!(estimate_tokens("abcdefgh"), 2);
+        assert_eq!(estimate_tokens("今日は Rust"), 5);
+    }
+
+    #[tokio::test]
+    async fn test_reduce_to_budget() {
+        let provider = CountingProvider {
+            requests: Mutex::new(vec![]),
+        };
+        let mut automation = test_config().automations.weekly_report;
+        automation.budget = Some(InputBudgetConfig {
+            max_chars: Some(200),
+            max_tokens: None,
+            chunk_chars: 140,
+        });
+
+        let short = entries(1, "短い日記");
+        assert_eq!(
+            reduce_to_budget(&provider, &automation, short.clone())
+                .await
+                .unwrap(),
+            short
+        );
+        assert!(provider.requests.lock().unwrap().is_empty());
+
+        let reduced = reduce_to_budget(&provider, &automation, entries(4, &"あ".repeat(30)))
+            .await
+            .unwrap();
+        assert!(reduced.starts_with("\n--- 要約 1/2 ---\n"), "{reduced}");
+        assert!(reduced.chars().count() <= 200);
+
+        let requests = provider.requests.lock().unwrap();
+        assert_eq!(requests.len(), 2);
+        assert!(!requests[0].json_mode);
+        assert_eq!(requests[0].system_prompt, CHUNK_SUMMARY_PROMPT);
+    }
+}
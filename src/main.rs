@@ -1,34 +1,106 @@
-use std::env;
-
 use axum::serve;
+use chrono::{DateTime, Duration, Utc};
 use dotenv::dotenv;
 use notion_ai_webhook::{
+    config::Config,
+    jobs::{scheduler::spawn_scheduler, worker::spawn_workers, JobQueue},
+    llm::LlmProviders,
     router::{router, AppState},
-    service::{GeminiService, NotionService},
+    schema::validate_database_schemas,
+    service::NotionService,
+    snapshot::{restore_snapshot, SnapshotStore},
+    store::Store,
 };
-use reqwest::Client;
-use tokio;
 
 #[tokio::main]
 async fn main() -> Result<(), Box<dyn std::error::Error>> {
     dotenv().ok();
-    let client = Client::new();
-    let notion_api_key = env::var("NOTION_API_KEY")?;
-    let gemini_api_key = env::var("GEMINI_API_KEY")?;
-    let diary_db_id = env::var("NOTION_DIARY_DB_ID")?;
-    let report_db_id = env::var("NOTION_REPORT_DB_ID")?;
+    let config = Config::load()?;
+
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    if let Some(command) = args.first() {
+        return run_command(&config, command, &args[1..]).await;
+    }
+
+    let notion_service = NotionService::new(&config)?;
+    validate_database_schemas(&notion_service).await?;
 
+    let store = Store::open(&config.store.database_path)?;
+    let job_queue = JobQueue::new(store.clone())
+        .with_max_attempts(config.jobs.max_attempts)
+        .with_dedup_window(Duration::seconds(config.jobs.dedup_window_seconds));
+    let recovered = job_queue.recover_interrupted()?;
+    if recovered > 0 {
+        println!("Re-queued {} interrupted jobs", recovered);
+    }
+
+    let snapshots = SnapshotStore::new(store.clone());
     let state = AppState {
-        notion_service: NotionService::new(client.clone(), notion_api_key, diary_db_id, report_db_id)?,
-        gemini_service: GeminiService::new(client.clone(), gemini_api_key)?,
+        notion_service,
+        llm_providers: LlmProviders::new(&config)?,
+        webhook_verification_token: config.server.webhook_verification_token.clone(),
+        admin_token: config.server.admin_token.clone(),
+        automations: config.automations.clone(),
+        calendar: config.calendar.clone(),
+        job_queue,
+        snapshots,
     };
+    spawn_workers(state.clone(), config.jobs.worker_concurrency);
+    spawn_scheduler(
+        state.job_queue.clone(),
+        config.scheduler.clone(),
+        config.calendar.timezone,
+    );
     let app = router(state);
 
-    let port = std::env::var("PORT").unwrap_or_else(|_| "8080".to_string());
-    let addr = format!("0.0.0.0:{}", port);
-
-    let listener = tokio::net::TcpListener::bind(&addr).await.unwrap();
+    let listener = tokio::net::TcpListener::bind(&config.server.bind_address)
+        .await
+        .unwrap();
     serve(listener, app).await.unwrap();
 
     Ok(())
 }
+
+// サーバーを起動せずに退避したブロックを扱うサブコマンド
+//   snapshots <page_id>             退避の一覧を表示する
+//   restore <page_id> [<RFC3339>]   指定時刻以前で最新の退避をページに戻す
+async fn run_command(
+    config: &Config,
+    command: &str,
+    args: &[String],
+) -> Result<(), Box<dyn std::error::Error>> {
+    let usage = "usage: notion-ai-webhook (snapshots <page_id> | restore <page_id> [<timestamp>])";
+    let Some(page_id) = args.first() else {
+        return Err(usage.into());
+    };
+    let snapshots = SnapshotStore::new(Store::open(&config.store.database_path)?);
+
+    match command {
+        "snapshots" => {
+            for snapshot in snapshots.list(page_id)? {
+                println!(
+                    "{}\t{}\t{} blocks",
+                    snapshot.id,
+                    snapshot.created_at.to_rfc3339(),
+                    snapshot.blocks.len()
+                );
+            }
+        }
+        "restore" => {
+            let at = args
+                .get(1)
+                .map(|timestamp| DateTime::parse_from_rfc3339(timestamp))
+                .transpose()?
+                .map(|timestamp| timestamp.with_timezone(&Utc));
+            let snapshot = snapshots
+                .find(page_id, at)?
+                .ok_or_else(|| format!("no snapshot for page {}", page_id))?;
+            let notion_service = NotionService::new(config)?;
+            let appended_block_ids = restore_snapshot(&notion_service, page_id, &snapshot).await?;
+            println!("Appended {} blocks", appended_block_ids.len());
+        }
+        _ => return Err(usage.into()),
+    }
+
+    Ok(())
+}
@@ -0,0 +1,354 @@
+use chrono::{DateTime, Datelike, Duration, Months, NaiveDate};
+use chrono_tz::Tz;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::error::{AppError, AppResult};
+
+/// 定期レポートの期間の種類
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ReportPeriodKind {
+    Week,
+    Month,
+    Quarter,
+    Year,
+}
+
+impl ReportPeriodKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Week => "week",
+            Self::Month => "month",
+            Self::Quarter => "quarter",
+            Self::Year => "year",
+        }
+    }
+
+    // レポートのタイトルの先頭に付ける。長い期間の要約で下位のレポートを探す目印にもなる
+    pub fn title_prefix(&self) -> &'static str {
+        match self {
+            Self::Week => "週報",
+            Self::Month => "月報",
+            Self::Quarter => "四半期報",
+            Self::Year => "年報",
+        }
+    }
+
+    /// 要約の材料にする一段細かい期間
+    pub fn finer(&self) -> Option<Self> {
+        match self {
+            Self::Week => None,
+            Self::Month => Some(Self::Week),
+            Self::Quarter => Some(Self::Month),
+            Self::Year => Some(Self::Quarter),
+        }
+    }
+}
+
+/// 週報の期間の取り方
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum WeekAlignment {
+    // 前日までの直近 7 日間
+    Rolling,
+    // 前日を含む ISO 週 (月曜〜日曜)
+    Iso,
+}
+
+/// payload で期間を指定する場合の値。start と end を両方指定すると任意の期間、
+/// start だけなら start を含む期間になる
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ReportPeriodRequest {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub kind: Option<ReportPeriodKind>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub start: Option<NaiveDate>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub end: Option<NaiveDate>,
+}
+
+impl ReportPeriodRequest {
+    /// Notion の日付プロパティの値 ({"start", "end"}) から期間を読む。
+    /// 時刻付きの値は timezone での日付にする
+    pub fn from_date_property(date: &Value, timezone: Tz) -> Option<Self> {
+        let parse = |key: &str| -> Option<NaiveDate> {
+            let value = date.get(key)?.as_str()?;
+            match DateTime::parse_from_rfc3339(value) {
+                Ok(datetime) => Some(datetime.with_timezone(&timezone).date_naive()),
+                Err(_) => value.parse().ok(),
+            }
+        };
+        Some(Self {
+            kind: None,
+            start: Some(parse("start")?),
+            end: parse("end"),
+        })
+    }
+}
+
+/// レポートの対象期間 (start と end を含む)
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ReportPeriod {
+    pub kind: ReportPeriodKind,
+    pub start: NaiveDate,
+    pub end: NaiveDate,
+    label: String,
+}
+
+impl ReportPeriod {
+    /// today を基準にした既定の期間。前日を含む期間をまとめる
+    /// (月曜や月初に実行すると前の週・月になる)
+    pub fn ending_at(kind: ReportPeriodKind, today: NaiveDate, week: WeekAlignment) -> Self {
+        let reference = today - Duration::days(1);
+        let (start, months, label) = match (kind, week) {
+            (ReportPeriodKind::Week, WeekAlignment::Rolling) => {
+                return Self::range(kind, reference - Duration::days(6), reference);
+            }
+            (ReportPeriodKind::Week, WeekAlignment::Iso) => {
+                let start =
+                    reference - Duration::days(reference.weekday().num_days_from_monday() as i64);
+                return Self {
+                    kind,
+                    start,
+                    end: start + Duration::days(6),
+                    label: reference.format("%G-W%V").to_string(),
+                };
+            }
+            (ReportPeriodKind::Month, _) => (
+                first_day(reference.year(), reference.month()),
+                1,
+                reference.format("%Y-%m").to_string(),
+            ),
+            (ReportPeriodKind::Quarter, _) => {
+                let quarter = reference.month0() / 3;
+                (
+                    first_day(reference.year(), quarter * 3 + 1),
+                    3,
+                    format!("{} Q{}", reference.year(), quarter + 1),
+                )
+            }
+            (ReportPeriodKind::Year, _) => (
+                first_day(reference.year(), 1),
+                12,
+                reference.year().to_string(),
+            ),
+        };
+        let end = start + Months::new(months) - Duration::days(1);
+        Self {
+            kind,
+            start,
+            end,
+            label,
+        }
+    }
+
+    /// date を含む期間。直近 7 日間の週は date から始まる 7 日間にする
+    pub fn containing(kind: ReportPeriodKind, date: NaiveDate, week: WeekAlignment) -> Self {
+        match (kind, week) {
+            (ReportPeriodKind::Week, WeekAlignment::Rolling) => {
+                Self::range(kind, date, date + Duration::days(6))
+            }
+            _ => Self::ending_at(kind, date + Duration::days(1), week),
+        }
+    }
+
+    /// payload の指定を優先し、無ければ default_kind の既定の期間にする
+    pub fn resolve(
+        request: Option<&ReportPeriodRequest>,
+        default_kind: ReportPeriodKind,
+        today: NaiveDate,
+        week: WeekAlignment,
+    ) -> AppResult<Self> {
+        let request = request.cloned().unwrap_or_default();
+        let kind = request.kind.unwrap_or(default_kind);
+        match (request.start, request.end) {
+            (None, None) => Ok(Self::ending_at(kind, today, week)),
+            (Some(start), None) => Ok(Self::containing(kind, start, week)),
+            (Some(start), Some(end)) if start <= end => Ok(Self::range(kind, start, end)),
+            (Some(start), Some(end)) => Err(AppError::InvalidPayload(format!(
+                "period start {} is after end {}",
+                start, end
+            ))),
+            _ => Err(AppError::InvalidPayload(
+                "period end must be given with start".to_string(),
+            )),
+        }
+    }
+
+    fn range(kind: ReportPeriodKind, start: NaiveDate, end: NaiveDate) -> Self {
+        Self {
+            kind,
+            start,
+            end,
+            label: format!("{} ~ {}", start, end),
+        }
+    }
+
+    pub fn title(&self) -> String {
+        format!("{} {}", self.kind.title_prefix(), self.label)
+    }
+}
+
+fn first_day(year: i32, month: u32) -> NaiveDate {
+    NaiveDate::from_ymd_opt(year, month, 1).expect("month is between 1 and 12")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn date(year: i32, month: u32, day: u32) -> NaiveDate {
+        NaiveDate::from_ymd_opt(year, month, day).unwrap()
+    }
+
+    #[test]
+    fn test_calendar_periods() {
+        let month = ReportPeriod::ending_at(
+            ReportPeriodKind::Month,
+            date(2024, 3, 1),
+            WeekAlignment::Iso,
+        );
+        assert_eq!(
+            (month.start, month.end),
+            (date(2024, 2, 1), date(2024, 2, 29))
+        );
+        assert_eq!(month.title(), "月報 2024-02");
+
+        let month = ReportPeriod::ending_at(
+            ReportPeriodKind::Month,
+            date(2024, 3, 20),
+            WeekAlignment::Iso,
+        );
+        assert_eq!(month.end, date(2024, 3, 31));
+
+        let quarter = ReportPeriod::ending_at(
+            ReportPeriodKind::Quarter,
+            date(2024, 7, 1),
+            WeekAlignment::Iso,
+        );
+        assert_eq!(
+            (quarter.start, quarter.end),
+            (date(2024, 4, 1), date(2024, 6, 30))
+        );
+        assert_eq!(quarter.title(), "四半期報 2024 Q2");
+
+        let year =
+            ReportPeriod::ending_at(ReportPeriodKind::Year, date(2025, 1, 1), WeekAlignment::Iso);
+        assert_eq!(
+            (year.start, year.end),
+            (date(2024, 1, 1), date(2024, 12, 31))
+        );
+        assert_eq!(year.title(), "年報 2024");
+    }
+
+    #[test]
+    fn test_week_periods() {
+        // 月曜に実行すると前週の月曜〜日曜、7 日ちょうどになる
+        let monday = date(2024, 3, 4);
+        let rolling =
+            ReportPeriod::ending_at(ReportPeriodKind::Week, monday, WeekAlignment::Rolling);
+        assert_eq!(
+            (rolling.start, rolling.end),
+            (date(2024, 2, 26), date(2024, 3, 3))
+        );
+        assert_eq!(rolling.title(), "週報 2024-02-26 ~ 2024-03-03");
+
+        let iso = ReportPeriod::ending_at(ReportPeriodKind::Week, monday, WeekAlignment::Iso);
+        assert_eq!((iso.start, iso.end), (rolling.start, rolling.end));
+        assert_eq!(iso.title(), "週報 2024-W09");
+
+        // 週の途中では、ISO 週は月曜に揃え、直近 7 日間はそのままずれる
+        let thursday = date(2024, 3, 7);
+        let rolling =
+            ReportPeriod::ending_at(ReportPeriodKind::Week, thursday, WeekAlignment::Rolling);
+        assert_eq!(
+            (rolling.start, rolling.end),
+            (date(2024, 2, 29), date(2024, 3, 6))
+        );
+        let iso = ReportPeriod::ending_at(ReportPeriodKind::Week, thursday, WeekAlignment::Iso);
+        assert_eq!((iso.start, iso.end), (date(2024, 3, 4), date(2024, 3, 10)));
+    }
+
+    #[test]
+    fn test_resolve_explicit_range() {
+        let request = ReportPeriodRequest {
+            kind: Some(ReportPeriodKind::Month),
+            start: Some(date(2024, 1, 10)),
+            end: Some(date(2024, 2, 9)),
+        };
+        let period = ReportPeriod::resolve(
+            Some(&request),
+            ReportPeriodKind::Week,
+            date(2024, 5, 1),
+            WeekAlignment::Rolling,
+        )
+        .unwrap();
+        assert_eq!(period.kind, ReportPeriodKind::Month);
+        assert_eq!(period.title(), "月報 2024-01-10 ~ 2024-02-09");
+
+        let anchored = ReportPeriodRequest {
+            kind: Some(ReportPeriodKind::Month),
+            start: Some(date(2024, 2, 15)),
+            end: None,
+        };
+        let period = ReportPeriod::resolve(
+            Some(&anchored),
+            ReportPeriodKind::Week,
+            date(2024, 5, 1),
+            WeekAlignment::Rolling,
+        )
+        .unwrap();
+        assert_eq!(period.title(), "月報 2024-02");
+
+        let reversed = ReportPeriodRequest {
+            start: Some(date(2024, 2, 1)),
+            end: Some(date(2024, 1, 1)),
+            ..Default::default()
+        };
+        assert!(ReportPeriod::resolve(
+            Some(&reversed),
+            ReportPeriodKind::Week,
+            date(2024, 5, 1),
+            WeekAlignment::Rolling
+        )
+        .is_err());
+
+        let default = ReportPeriod::resolve(
+            None,
+            ReportPeriodKind::Week,
+            date(2024, 5, 1),
+            WeekAlignment::Rolling,
+        )
+        .unwrap();
+        assert_eq!(default.kind, ReportPeriodKind::Week);
+        assert_eq!(default.end, date(2024, 4, 30));
+    }
+
+    #[test]
+    fn test_request_from_date_property() {
+        let range = ReportPeriodRequest::from_date_property(
+            &json!({"start": "2024-02-01", "end": "2024-02-29", "time_zone": null}),
+            chrono_tz::Asia::Tokyo,
+        )
+        .unwrap();
+        assert_eq!(
+            (range.start, range.end),
+            (Some(date(2024, 2, 1)), Some(date(2024, 2, 29)))
+        );
+
+        // UTC では前日でも、設定したタイムゾーンの日付で読む
+        let datetime = ReportPeriodRequest::from_date_property(
+            &json!({"start": "2024-02-29T23:30:00.000+00:00", "end": null}),
+            chrono_tz::Asia::Tokyo,
+        )
+        .unwrap();
+        assert_eq!(
+            (datetime.start, datetime.end),
+            (Some(date(2024, 3, 1)), None)
+        );
+
+        assert!(ReportPeriodRequest::from_date_property(&Value::Null, chrono_tz::UTC).is_none());
+    }
+}
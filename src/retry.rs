@@ -0,0 +1,277 @@
+use std::time::Duration;
+
+use reqwest::{header::HeaderMap, header::RETRY_AFTER, RequestBuilder, Response, StatusCode};
+use tokio::{sync::Mutex, time::Instant};
+
+use crate::error::AppResult;
+
+#[derive(Debug, Clone)]
+pub struct RetryPolicy {
+    // 初回を含めた最大試行回数
+    pub max_attempts: u32,
+    pub base_delay: Duration,
+    pub max_delay: Duration,
+    // サーバーが指定した Retry-After がこれより長ければ、待たずに諦める
+    pub max_retry_after: Duration,
+    // false の場合、処理されたか分からない失敗 (タイムアウトや 5xx) では再送しない
+    pub idempotent: bool,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            max_attempts: 5,
+            base_delay: Duration::from_millis(500),
+            max_delay: Duration::from_secs(30),
+            max_retry_after: Duration::from_secs(300),
+            idempotent: true,
+        }
+    }
+}
+
+impl RetryPolicy {
+    pub fn with_max_attempts(mut self, max_attempts: u32) -> Self {
+        self.max_attempts = max_attempts.max(1);
+        self
+    }
+
+    /// ページ作成やブロック追加など、二重に送ると重複する書き込み用。
+    /// 429 と接続前のエラーだけを再送する
+    pub fn non_idempotent(mut self) -> Self {
+        self.idempotent = false;
+        self
+    }
+
+    fn should_retry_status(&self, status: StatusCode) -> bool {
+        if self.idempotent {
+            is_retryable_status(status)
+        } else {
+            status == StatusCode::TOO_MANY_REQUESTS
+        }
+    }
+
+    fn should_retry_error(&self, error: &reqwest::Error) -> bool {
+        error.is_connect() || (self.idempotent && error.is_timeout())
+    }
+
+    /// attempt 回目の失敗後に待つ時間。Retry-After があればその時間をそのまま待ち、
+    /// なければ指数バックオフに full jitter をかける。
+    /// Retry-After が max_retry_after を超える場合は None (リトライしない)
+    pub fn delay_for(&self, attempt: u32, retry_after: Option<Duration>) -> Option<Duration> {
+        if let Some(retry_after) = retry_after {
+            return (retry_after <= self.max_retry_after).then_some(retry_after);
+        }
+        let exponential = self
+            .base_delay
+            .saturating_mul(2u32.saturating_pow(attempt.saturating_sub(1)))
+            .min(self.max_delay);
+        Some(exponential.mul_f64(rand::random::<f64>()))
+    }
+}
+
+pub fn is_retryable_status(status: StatusCode) -> bool {
+    matches!(
+        status,
+        StatusCode::TOO_MANY_REQUESTS
+            | StatusCode::INTERNAL_SERVER_ERROR
+            | StatusCode::BAD_GATEWAY
+            | StatusCode::SERVICE_UNAVAILABLE
+            | StatusCode::GATEWAY_TIMEOUT
+    )
+}
+
+// Notion の Retry-After は秒数で返ってくる
+pub fn parse_retry_after(headers: &HeaderMap) -> Option<Duration> {
+    headers
+        .get(RETRY_AFTER)?
+        .to_str()
+        .ok()?
+        .trim()
+        .parse::<f64>()
+        .ok()
+        .filter(|seconds| seconds.is_finite() && *seconds >= 0.0)
+        .map(Duration::from_secs_f64)
+}
+
+/// トークンバケット方式のクライアント側レートリミッタ
+#[derive(Debug)]
+pub struct RateLimiter {
+    requests_per_second: f64,
+    capacity: f64,
+    bucket: Mutex<Bucket>,
+}
+
+#[derive(Debug)]
+struct Bucket {
+    tokens: f64,
+    last_refill: Instant,
+}
+
+impl RateLimiter {
+    pub fn new(requests_per_second: f64, burst: u32) -> Self {
+        let capacity = f64::from(burst.max(1));
+        Self {
+            requests_per_second,
+            capacity,
+            bucket: Mutex::new(Bucket {
+                tokens: capacity,
+                last_refill: Instant::now(),
+            }),
+        }
+    }
+
+    pub async fn acquire(&self) {
+        let mut bucket = self.bucket.lock().await;
+        loop {
+            let now = Instant::now();
+            let elapsed = now.duration_since(bucket.last_refill).as_secs_f64();
+            bucket.tokens = (bucket.tokens + elapsed * self.requests_per_second).min(self.capacity);
+            bucket.last_refill = now;
+
+            if bucket.tokens >= 1.0 {
+                bucket.tokens -= 1.0;
+                return;
+            }
+
+            // ロックを保持したまま待つことで、待機中のリクエストを到着順に流す
+            let wait = (1.0 - bucket.tokens) / self.requests_per_second;
+            tokio::time::sleep(Duration::from_secs_f64(wait)).await;
+        }
+    }
+}
+
+/// リトライ可能なステータスや接続エラーの場合に、ポリシーに従って再送する
+pub async fn send_with_retry(
+    policy: &RetryPolicy,
+    rate_limiter: Option<&RateLimiter>,
+    request: RequestBuilder,
+) -> AppResult<Response> {
+    let mut attempt = 1;
+    loop {
+        // JSON ボディは複製できるが、念のため複製できない場合は 1 回だけ送る
+        let Some(current) = request.try_clone() else {
+            if let Some(rate_limiter) = rate_limiter {
+                rate_limiter.acquire().await;
+            }
+            return Ok(request.send().await?);
+        };
+
+        if let Some(rate_limiter) = rate_limiter {
+            rate_limiter.acquire().await;
+        }
+
+        let can_retry = attempt < policy.max_attempts;
+        let delay = match current.send().await {
+            Ok(response) if can_retry && policy.should_retry_status(response.status()) => {
+                let Some(delay) = policy.delay_for(attempt, parse_retry_after(response.headers()))
+                else {
+                    println!(
+                        "Giving up after status {}: Retry-After exceeds {:?}",
+                        response.status(),
+                        policy.max_retry_after
+                    );
+                    return Ok(response);
+                };
+                println!(
+                    "Retrying request after status {} (attempt {}/{}, waiting {:?})",
+                    response.status(),
+                    attempt,
+                    policy.max_attempts,
+                    delay
+                );
+                delay
+            }
+            Ok(response) => return Ok(response),
+            Err(error) if can_retry && policy.should_retry_error(&error) => {
+                let delay = policy.delay_for(attempt, None).unwrap_or_default();
+                println!(
+                    "Retrying request after error: {} (attempt {}/{}, waiting {:?})",
+                    error, attempt, policy.max_attempts, delay
+                );
+                delay
+            }
+            Err(error) => return Err(error.into()),
+        };
+
+        tokio::time::sleep(delay).await;
+        attempt += 1;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use reqwest::header::HeaderValue;
+
+    #[test]
+    fn test_delay_honors_retry_after() {
+        let policy = RetryPolicy::default();
+        assert_eq!(
+            policy.delay_for(1, Some(Duration::from_secs(2))),
+            Some(Duration::from_secs(2))
+        );
+        // max_delay より長くても、指定された時間をそのまま待つ
+        assert_eq!(
+            policy.delay_for(1, Some(Duration::from_secs(60))),
+            Some(Duration::from_secs(60))
+        );
+        assert_eq!(policy.delay_for(1, Some(Duration::from_secs(600))), None);
+    }
+
+    #[test]
+    fn test_backoff_delay_is_bounded() {
+        let policy = RetryPolicy::default();
+        for attempt in 1..=10 {
+            let ceiling = policy
+                .base_delay
+                .saturating_mul(2u32.pow(attempt - 1))
+                .min(policy.max_delay);
+            assert!(policy.delay_for(attempt, None).unwrap() <= ceiling);
+        }
+    }
+
+    #[test]
+    fn test_parse_retry_after() {
+        let mut headers = HeaderMap::new();
+        assert_eq!(parse_retry_after(&headers), None);
+
+        headers.insert(RETRY_AFTER, HeaderValue::from_static("3"));
+        assert_eq!(parse_retry_after(&headers), Some(Duration::from_secs(3)));
+
+        headers.insert(
+            RETRY_AFTER,
+            HeaderValue::from_static("Wed, 21 Oct 2015 07:28:00 GMT"),
+        );
+        assert_eq!(parse_retry_after(&headers), None);
+    }
+
+    #[test]
+    fn test_retryable_statuses() {
+        assert!(is_retryable_status(StatusCode::TOO_MANY_REQUESTS));
+        assert!(is_retryable_status(StatusCode::BAD_GATEWAY));
+        assert!(is_retryable_status(StatusCode::SERVICE_UNAVAILABLE));
+        assert!(!is_retryable_status(StatusCode::NOT_FOUND));
+        assert!(!is_retryable_status(StatusCode::BAD_REQUEST));
+    }
+
+    #[test]
+    fn test_non_idempotent_requests_only_retry_rate_limits() {
+        let policy = RetryPolicy::default().non_idempotent();
+        assert!(policy.should_retry_status(StatusCode::TOO_MANY_REQUESTS));
+        assert!(!policy.should_retry_status(StatusCode::BAD_GATEWAY));
+        assert!(RetryPolicy::default().should_retry_status(StatusCode::BAD_GATEWAY));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_rate_limiter_spaces_requests() {
+        let limiter = RateLimiter::new(3.0, 1);
+        let start = Instant::now();
+        for _ in 0..4 {
+            limiter.acquire().await;
+        }
+        // 最初の 1 件はバケットから、残り 3 件は 1/3 秒ごと
+        let elapsed = start.elapsed();
+        assert!(elapsed >= Duration::from_millis(990), "{elapsed:?}");
+        assert!(elapsed < Duration::from_millis(1100), "{elapsed:?}");
+    }
+}
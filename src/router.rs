@@ -1,24 +1,401 @@
 use crate::{
     automation::{
-        diary::handle_diary_automation, review::handle_review_automation,
-        weekly_report::handle_weekly_report,
+        diary::handle_diary_automation,
+        report::{handle_periodic_report, handle_weekly_report},
+        review::handle_review_automation,
     },
-    service::{GeminiService, NotionService},
+    config::{AutomationsConfig, CalendarConfig},
+    jobs::{
+        handlers::{get_job, list_jobs},
+        JobQueue,
+    },
+    llm::LlmProviders,
+    service::NotionService,
+    snapshot::{
+        handlers::{list_snapshots, restore_page_snapshot},
+        SnapshotStore,
+    },
+    types::NotionVerificationRequest,
+};
+use axum::{
+    body::{to_bytes, Body},
+    extract::{Request, State},
+    middleware::{from_fn_with_state, Next},
+    response::{IntoResponse, Response},
+    routing::{get, post},
+    Router,
 };
-use axum::{routing::post, Router};
+use hmac::{Hmac, KeyInit, Mac};
+use reqwest::{header::AUTHORIZATION, StatusCode};
+use sha2::Sha256;
+
+pub const NOTION_SIGNATURE_HEADER: &str = "x-notion-signature";
+const MAX_WEBHOOK_BODY_BYTES: usize = 1024 * 1024;
 
 #[derive(Clone)]
 pub struct AppState {
     pub notion_service: NotionService,
-    pub gemini_service: GeminiService,
+    pub llm_providers: LlmProviders,
+    // サブスクリプション作成時のハンドシェイクで受け取る verification_token
+    pub webhook_verification_token: Option<String>,
+    // 管理用 API (/jobs, /snapshots) の Bearer トークン
+    pub admin_token: Option<String>,
+    // 自動化ごとのモデル・温度・プロンプト
+    pub automations: AutomationsConfig,
+    // レポートの期間を計算するタイムゾーンと週の区切り方
+    pub calendar: CalendarConfig,
+    pub job_queue: JobQueue,
+    // AI セクションを置き換える前に退避したブロック
+    pub snapshots: SnapshotStore,
 }
 
 pub fn router(state: AppState) -> Router {
     let webhook_routes = Router::new()
         .route("/diary", post(handle_diary_automation))
         .route("/diary-weekly-report", post(handle_weekly_report))
+        .route("/report/{period}", post(handle_periodic_report))
         .route("/review", post(handle_review_automation))
+        .route_layer(from_fn_with_state(state.clone(), verify_notion_signature))
+        .with_state(state.clone());
+
+    let job_routes = Router::new()
+        .route("/", get(list_jobs))
+        .route("/{id}", get(get_job))
+        .route_layer(from_fn_with_state(state.clone(), require_admin_token))
+        .with_state(state.clone());
+
+    let snapshot_routes = Router::new()
+        .route("/{page_id}", get(list_snapshots))
+        .route("/{page_id}/restore", post(restore_page_snapshot))
+        .route_layer(from_fn_with_state(state.clone(), require_admin_token))
         .with_state(state);
 
-    Router::<()>::new().nest("/webhook", webhook_routes)
-}
\ No newline at end of file
+    Router::<()>::new()
+        .nest("/webhook", webhook_routes)
+        .nest("/jobs", job_routes)
+        .nest("/snapshots", snapshot_routes)
+}
+
+// 署名検証に通ったリクエストだけをハンドラに渡す
+async fn verify_notion_signature(
+    State(state): State<AppState>,
+    request: Request,
+    next: Next,
+) -> Response {
+    let (parts, body) = request.into_parts();
+    let bytes = match to_bytes(body, MAX_WEBHOOK_BODY_BYTES).await {
+        Ok(bytes) => bytes,
+        Err(_) => return StatusCode::PAYLOAD_TOO_LARGE.into_response(),
+    };
+
+    // 初回のハンドシェイクは署名なしで届くため、トークンをログに出して終了する
+    if let Ok(challenge) = serde_json::from_slice::<NotionVerificationRequest>(&bytes) {
+        println!(
+            "Received Notion webhook verification token: {}",
+            challenge.verification_token
+        );
+        return StatusCode::OK.into_response();
+    }
+
+    let Some(token) = state.webhook_verification_token.as_deref() else {
+        println!("Rejected webhook: verification token is not configured");
+        return StatusCode::UNAUTHORIZED.into_response();
+    };
+
+    let signature = parts
+        .headers
+        .get(NOTION_SIGNATURE_HEADER)
+        .and_then(|value| value.to_str().ok());
+
+    match signature {
+        Some(signature) if is_valid_signature(token, &bytes, signature) => {
+            next.run(Request::from_parts(parts, Body::from(bytes))).await
+        }
+        _ => {
+            println!("Rejected webhook: missing or invalid signature");
+            StatusCode::UNAUTHORIZED.into_response()
+        }
+    }
+}
+
+// 管理用トークンが一致するリクエストだけをハンドラに渡す
+async fn require_admin_token(
+    State(state): State<AppState>,
+    request: Request,
+    next: Next,
+) -> Response {
+    let Some(token) = state.admin_token.as_deref() else {
+        println!("Rejected admin request: admin token is not configured");
+        return StatusCode::UNAUTHORIZED.into_response();
+    };
+
+    let bearer = request
+        .headers()
+        .get(AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "));
+
+    match bearer {
+        Some(bearer) if constant_time_eq(bearer.trim().as_bytes(), token.as_bytes()) => {
+            next.run(request).await
+        }
+        _ => {
+            println!("Rejected admin request: missing or invalid bearer token");
+            StatusCode::UNAUTHORIZED.into_response()
+        }
+    }
+}
+
+// 一致するまでの時間からトークンを推測されないよう、全バイトを比較する
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0, |acc, (x, y)| acc | (x ^ y)) == 0
+}
+
+/// `X-Notion-Signature` (`sha256=<hex>`) がボディの HMAC-SHA256 と一致するか検証する
+pub fn is_valid_signature(token: &str, body: &[u8], signature: &str) -> bool {
+    let Some(expected) = signature
+        .strip_prefix("sha256=")
+        .and_then(|hex_digest| hex::decode(hex_digest).ok())
+    else {
+        return false;
+    };
+
+    let Ok(mut mac) = Hmac::<Sha256>::new_from_slice(token.as_bytes()) else {
+        return false;
+    };
+    mac.update(body);
+    mac.verify_slice(&expected).is_ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::http::Request as HttpRequest;
+    use crate::{config::test_config, jobs::JobKind, store::Store};
+    use tower::ServiceExt;
+
+    const TOKEN: &str = "secret_test_token";
+    const ADMIN_TOKEN: &str = "admin_test_token";
+
+    fn sign(body: &[u8]) -> String {
+        let mut mac = Hmac::<Sha256>::new_from_slice(TOKEN.as_bytes()).unwrap();
+        mac.update(body);
+        format!("sha256={}", hex::encode(mac.finalize().into_bytes()))
+    }
+
+    fn test_state(token: Option<&str>) -> AppState {
+        let config = test_config();
+        AppState {
+            notion_service: NotionService::new(&config).unwrap(),
+            llm_providers: LlmProviders::new(&config).unwrap(),
+            webhook_verification_token: token.map(str::to_string),
+            admin_token: Some(ADMIN_TOKEN.to_string()),
+            automations: config.automations,
+            calendar: config.calendar,
+            job_queue: JobQueue::new(Store::open_in_memory().unwrap()),
+            snapshots: SnapshotStore::new(Store::open_in_memory().unwrap()),
+        }
+    }
+
+    fn webhook_request(body: &str, signature: Option<String>) -> HttpRequest<Body> {
+        let mut builder = HttpRequest::post("/webhook/diary")
+            .header("content-type", "application/json");
+        if let Some(signature) = signature {
+            builder = builder.header(NOTION_SIGNATURE_HEADER, signature);
+        }
+        builder.body(Body::from(body.to_string())).unwrap()
+    }
+
+    fn admin_get(uri: &str, token: Option<&str>) -> HttpRequest<Body> {
+        let mut builder = HttpRequest::get(uri);
+        if let Some(token) = token {
+            builder = builder.header(AUTHORIZATION, format!("Bearer {}", token));
+        }
+        builder.body(Body::empty()).unwrap()
+    }
+
+    #[test]
+    fn test_is_valid_signature() {
+        let body = br#"{"data":{"id":"page"}}"#;
+        assert!(is_valid_signature(TOKEN, body, &sign(body)));
+        assert!(!is_valid_signature(TOKEN, b"tampered", &sign(body)));
+        assert!(!is_valid_signature("other", body, &sign(body)));
+        assert!(!is_valid_signature(TOKEN, body, "sha256=zz"));
+        assert!(!is_valid_signature(TOKEN, body, &sign(body)[7..]));
+    }
+
+    #[tokio::test]
+    async fn test_unsigned_request_is_rejected() {
+        let app = router(test_state(Some(TOKEN)));
+        let response = app
+            .oneshot(webhook_request(r#"{"data":{"id":"page"}}"#, None))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+    }
+
+    #[tokio::test]
+    async fn test_mis_signed_request_is_rejected() {
+        let app = router(test_state(Some(TOKEN)));
+        let response = app
+            .oneshot(webhook_request(
+                r#"{"data":{"id":"page"}}"#,
+                Some(sign(b"something else")),
+            ))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+    }
+
+    #[tokio::test]
+    async fn test_request_is_rejected_without_configured_token() {
+        let body = r#"{"data":{"id":"page"}}"#;
+        let app = router(test_state(None));
+        let response = app
+            .oneshot(webhook_request(body, Some(sign(body.as_bytes()))))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+    }
+
+    async fn read_json(response: Response) -> serde_json::Value {
+        let bytes = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        serde_json::from_slice(&bytes).unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_signed_request_returns_job_id() {
+        let body = r#"{"data":{"id":"page-1"}}"#;
+        let state = test_state(Some(TOKEN));
+        let response = router(state.clone())
+            .oneshot(webhook_request(body, Some(sign(body.as_bytes()))))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+
+        let accepted = read_json(response).await;
+        assert_eq!(accepted["state"], "queued");
+        let job_id = accepted["job_id"].as_i64().unwrap();
+
+        let response = router(state.clone())
+            .oneshot(admin_get(&format!("/jobs/{}", job_id), Some(ADMIN_TOKEN)))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let job = read_json(response).await;
+        assert_eq!(job["kind"], "diary");
+        assert_eq!(job["state"], "queued");
+
+        let response = router(state)
+            .oneshot(admin_get("/jobs?page_id=page-1", Some(ADMIN_TOKEN)))
+            .await
+            .unwrap();
+        let jobs = read_json(response).await;
+        assert_eq!(jobs.as_array().unwrap().len(), 1);
+        assert_eq!(jobs[0]["id"], job_id);
+    }
+
+    #[tokio::test]
+    async fn test_periodic_report_validates_period() {
+        let state = test_state(Some(TOKEN));
+        let report_request = |body: &str| {
+            HttpRequest::post("/webhook/report/month")
+                .header("content-type", "application/json")
+                .header(NOTION_SIGNATURE_HEADER, sign(body.as_bytes()))
+                .body(Body::from(body.to_string()))
+                .unwrap()
+        };
+
+        let body = r#"{"data":{"id":"page-1"},"period":{"end":"2024-01-01"}}"#;
+        let response = router(state.clone())
+            .oneshot(report_request(body))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+
+        let body = r#"{"data":{"id":"page-1"}}"#;
+        let response = router(state.clone())
+            .oneshot(report_request(body))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let job_id = read_json(response).await["job_id"].as_i64().unwrap();
+        let job = state.job_queue.get(job_id).unwrap().unwrap();
+        assert_eq!(job.kind, JobKind::PeriodicReport);
+        assert_eq!(job.payload["period"]["kind"], "month");
+    }
+
+    #[tokio::test]
+    async fn test_unknown_job_returns_not_found() {
+        let response = router(test_state(None))
+            .oneshot(admin_get("/jobs/42", Some(ADMIN_TOKEN)))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+    }
+
+    #[tokio::test]
+    async fn test_jobs_require_admin_token() {
+        for token in [None, Some("wrong")] {
+            let response = router(test_state(None))
+                .oneshot(admin_get("/jobs", token))
+                .await
+                .unwrap();
+            assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+        }
+
+        let mut state = test_state(None);
+        state.admin_token = None;
+        let response = router(state)
+            .oneshot(admin_get("/jobs", Some(ADMIN_TOKEN)))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+    }
+
+    #[tokio::test]
+    async fn test_restore_without_snapshot_returns_not_found() {
+        let response = router(test_state(None))
+            .oneshot(
+                HttpRequest::post("/snapshots/page-1/restore")
+                    .header(AUTHORIZATION, format!("Bearer {}", ADMIN_TOKEN))
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+    }
+
+    #[tokio::test]
+    async fn test_snapshots_require_admin_token() {
+        let response = router(test_state(None))
+            .oneshot(admin_get("/snapshots/page-1", None))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+
+        let response = router(test_state(None))
+            .oneshot(
+                HttpRequest::post("/snapshots/page-1/restore")
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+    }
+
+    #[tokio::test]
+    async fn test_verification_challenge_is_acknowledged() {
+        let app = router(test_state(None));
+        let response = app
+            .oneshot(webhook_request(
+                r#"{"verification_token":"secret_new"}"#,
+                None,
+            ))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+    }
+}
@@ -0,0 +1,282 @@
+use serde_json::Value;
+
+use crate::{
+    api::retrieve_database,
+    error::{AppError, AppResult},
+    service::NotionService,
+    types::NotionDatabase,
+};
+
+/// 論理フィールドと実際のプロパティ名の対応。検証時に型もあわせて確認する
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PropertyMapping {
+    // 設定キー (エラーメッセージに使う)
+    pub key: &'static str,
+    pub name: String,
+    // 許容する Notion のプロパティ型
+    pub allowed_types: &'static [&'static str],
+}
+
+impl PropertyMapping {
+    fn new(key: &'static str, name: &str, allowed_types: &'static [&'static str]) -> Self {
+        Self {
+            key,
+            name: name.to_string(),
+            allowed_types,
+        }
+    }
+}
+
+/// 日記データベースのプロパティ名
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DiaryDatabaseSchema {
+    pub date: PropertyMapping,
+    pub title: PropertyMapping,
+    pub tags: Option<PropertyMapping>,
+    pub mood: Option<PropertyMapping>,
+}
+
+impl Default for DiaryDatabaseSchema {
+    fn default() -> Self {
+        Self {
+            date: PropertyMapping::new("diary.properties.date", "日付", &["date"]),
+            title: PropertyMapping::new("diary.properties.title", "名前", &["title"]),
+            tags: None,
+            mood: None,
+        }
+    }
+}
+
+impl DiaryDatabaseSchema {
+    pub fn with_date(mut self, name: &str) -> Self {
+        self.date.name = name.to_string();
+        self
+    }
+
+    pub fn with_title(mut self, name: &str) -> Self {
+        self.title.name = name.to_string();
+        self
+    }
+
+    pub fn with_tags(mut self, name: &str) -> Self {
+        self.tags = Some(PropertyMapping::new(
+            "diary.properties.tags",
+            name,
+            &["multi_select", "select"],
+        ));
+        self
+    }
+
+    pub fn with_mood(mut self, name: &str) -> Self {
+        self.mood = Some(PropertyMapping::new(
+            "diary.properties.mood",
+            name,
+            &["select", "status", "rich_text", "number"],
+        ));
+        self
+    }
+
+    fn mappings(&self) -> Vec<&PropertyMapping> {
+        let mut mappings = vec![&self.date, &self.title];
+        mappings.extend(self.tags.iter());
+        mappings.extend(self.mood.iter());
+        mappings
+    }
+}
+
+/// レポートデータベースのプロパティ名
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ReportDatabaseSchema {
+    pub title: PropertyMapping,
+    pub date: PropertyMapping,
+}
+
+impl Default for ReportDatabaseSchema {
+    fn default() -> Self {
+        Self {
+            title: PropertyMapping::new("report.properties.title", "名前", &["title"]),
+            date: PropertyMapping::new("report.properties.date", "日付", &["date"]),
+        }
+    }
+}
+
+impl ReportDatabaseSchema {
+    pub fn with_title(mut self, name: &str) -> Self {
+        self.title.name = name.to_string();
+        self
+    }
+
+    pub fn with_date(mut self, name: &str) -> Self {
+        self.date.name = name.to_string();
+        self
+    }
+
+    fn mappings(&self) -> Vec<&PropertyMapping> {
+        vec![&self.title, &self.date]
+    }
+}
+
+/// 起動時に、設定したプロパティが各データベースに存在し型が合っているか確認する
+pub async fn validate_database_schemas(service: &NotionService) -> AppResult<()> {
+    let diary_database = retrieve_database(service, &service.diary_db_id).await?;
+    validate_properties("diary", &diary_database, &service.diary_schema.mappings())?;
+
+    // 期間ごとのデータベースも同じプロパティ名で書き込むので、重複を除いてすべて確認する
+    let mut report_db_ids = vec![service.report_db_id.as_str()];
+    for database_id in service.report_databases.values() {
+        if !report_db_ids.contains(&database_id.as_str()) {
+            report_db_ids.push(database_id);
+        }
+    }
+    for database_id in report_db_ids {
+        let report_database = retrieve_database(service, database_id).await?;
+        validate_properties(
+            "report",
+            &report_database,
+            &service.report_schema.mappings(),
+        )?;
+    }
+    Ok(())
+}
+
+fn validate_properties(
+    database_label: &str,
+    database: &NotionDatabase,
+    mappings: &[&PropertyMapping],
+) -> AppResult<()> {
+    let problems: Vec<String> = mappings
+        .iter()
+        .filter_map(|mapping| match database.properties.get(&mapping.name) {
+            None => Some(format!(
+                "{}: property \"{}\" does not exist in the {} database ({})",
+                mapping.key, mapping.name, database_label, database.id
+            )),
+            Some(property)
+                if !mapping
+                    .allowed_types
+                    .contains(&property.property_type.as_str()) =>
+            {
+                Some(format!(
+                    "{}: property \"{}\" in the {} database has type {}, expected {}",
+                    mapping.key,
+                    mapping.name,
+                    database_label,
+                    property.property_type,
+                    mapping.allowed_types.join(" or ")
+                ))
+            }
+            Some(_) => None,
+        })
+        .collect();
+
+    if problems.is_empty() {
+        Ok(())
+    } else {
+        Err(AppError::Config(problems.join("; ")))
+    }
+}
+
+/// ページのプロパティ値を、プロンプトに載せるための文字列にする
+pub fn property_text(properties: &Value, name: &str) -> Option<String> {
+    let property = properties.get(name)?;
+    let value = property.get(property.get("type")?.as_str()?)?;
+    let text = match value {
+        Value::Array(items) => {
+            // title / rich_text は plain_text、multi_select は name を持つ
+            let separator = if items.iter().any(|item| item.get("plain_text").is_some()) {
+                ""
+            } else {
+                ", "
+            };
+            items
+                .iter()
+                .filter_map(|item| {
+                    item.get("plain_text")
+                        .or_else(|| item.get("name"))
+                        .and_then(Value::as_str)
+                })
+                .collect::<Vec<_>>()
+                .join(separator)
+        }
+        Value::Object(object) => object
+            .get("name")
+            .or_else(|| object.get("start"))
+            .and_then(Value::as_str)?
+            .to_string(),
+        Value::Number(number) => number.to_string(),
+        Value::Bool(checked) => checked.to_string(),
+        Value::String(text) => text.clone(),
+        Value::Null => return None,
+    };
+    Some(text).filter(|text| !text.is_empty())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn database(properties: Value) -> NotionDatabase {
+        serde_json::from_value(json!({ "id": "db", "properties": properties })).unwrap()
+    }
+
+    #[test]
+    fn test_validate_default_schema() {
+        let database = database(json!({
+            "名前": { "id": "title", "type": "title", "title": {} },
+            "日付": { "id": "a", "type": "date", "date": {} },
+        }));
+        let schema = DiaryDatabaseSchema::default();
+        assert!(validate_properties("diary", &database, &schema.mappings()).is_ok());
+    }
+
+    #[test]
+    fn test_validate_reports_missing_and_mistyped_properties() {
+        let database = database(json!({
+            "Name": { "id": "title", "type": "title", "title": {} },
+            "Date": { "id": "a", "type": "rich_text", "rich_text": {} },
+        }));
+        let schema = DiaryDatabaseSchema::default()
+            .with_title("Name")
+            .with_date("Date")
+            .with_tags("Tags");
+        let error = validate_properties("diary", &database, &schema.mappings())
+            .unwrap_err()
+            .to_string();
+        assert!(
+            error.contains("diary.properties.date: property \"Date\" in the diary database has type rich_text, expected date"),
+            "{error}"
+        );
+        assert!(
+            error.contains("diary.properties.tags: property \"Tags\" does not exist"),
+            "{error}"
+        );
+        assert!(!error.contains("diary.properties.title"), "{error}");
+    }
+
+    #[test]
+    fn test_property_text() {
+        let properties = json!({
+            "Name": { "type": "title", "title": [{ "plain_text": "朝の" }, { "plain_text": "散歩" }] },
+            "Date": { "type": "date", "date": { "start": "2025-01-06", "end": null } },
+            "Tags": { "type": "multi_select", "multi_select": [{ "name": "運動" }, { "name": "家族" }] },
+            "Mood": { "type": "select", "select": { "name": "良い" } },
+            "Empty": { "type": "select", "select": null },
+        });
+        assert_eq!(
+            property_text(&properties, "Name").as_deref(),
+            Some("朝の散歩")
+        );
+        assert_eq!(
+            property_text(&properties, "Date").as_deref(),
+            Some("2025-01-06")
+        );
+        assert_eq!(
+            property_text(&properties, "Tags").as_deref(),
+            Some("運動, 家族")
+        );
+        assert_eq!(property_text(&properties, "Mood").as_deref(), Some("良い"));
+        assert_eq!(property_text(&properties, "Empty"), None);
+        assert_eq!(property_text(&properties, "Missing"), None);
+    }
+}
@@ -1,40 +1,170 @@
+use std::{collections::HashMap, sync::Arc, time::Duration};
+
 use reqwest::Client;
 
+use crate::{
+    config::Config,
+    error::AppResult,
+    period::ReportPeriodKind,
+    retry::{RateLimiter, RetryPolicy},
+    schema::{DiaryDatabaseSchema, ReportDatabaseSchema},
+};
+
 #[derive(Clone)]
 pub struct NotionService {
     pub client: Client,
     pub api_key: String,
     pub diary_db_id: String,
     pub report_db_id: String,
+    // 期間ごとに report_db_id の代わりに使うデータベース
+    pub report_databases: HashMap<ReportPeriodKind, String>,
+    pub diary_schema: DiaryDatabaseSchema,
+    pub report_schema: ReportDatabaseSchema,
+    // webhook を送ったページで対象期間を指定する日付プロパティ
+    pub report_period_property: Option<String>,
+    // ページ直下を 0 として、何階層目の子ブロックまで取得するか
+    pub max_block_depth: usize,
+    // 複数のページを取得するときの同時実行数。リクエストの頻度は rate_limiter が抑える
+    pub fetch_concurrency: usize,
+    pub retry_policy: RetryPolicy,
+    // クローンしたサービス間で共有し、プロセス全体でレートを制限する
+    pub rate_limiter: Arc<RateLimiter>,
 }
 
 impl NotionService {
-    pub fn new(
-        client: Client,
-        api_key: String,
-        diary_db_id: String,
-        report_db_id: String,
-    ) -> Result<Self, Box<dyn std::error::Error>> {
+    pub fn new(config: &Config) -> AppResult<Self> {
+        let client = Client::builder()
+            .timeout(Duration::from_secs(config.notion.timeout_seconds))
+            .build()?;
+
+        let properties = &config.diary.properties;
+        let mut diary_schema = DiaryDatabaseSchema::default()
+            .with_date(&properties.date)
+            .with_title(&properties.title);
+        if let Some(tags) = &properties.tags {
+            diary_schema = diary_schema.with_tags(tags);
+        }
+        if let Some(mood) = &properties.mood {
+            diary_schema = diary_schema.with_mood(mood);
+        }
+        let report_schema = ReportDatabaseSchema::default()
+            .with_title(&config.report.properties.title)
+            .with_date(&config.report.properties.date);
+
+        let requests_per_second = config.notion.requests_per_second;
         Ok(Self {
             client,
-            api_key: api_key.trim().to_string(),
-            diary_db_id: diary_db_id.trim().to_string(),
-            report_db_id: report_db_id.trim().to_string(),
+            api_key: config.notion.api_key.trim().to_string(),
+            diary_db_id: config.diary.database_id.trim().to_string(),
+            report_db_id: config.report.database_id.trim().to_string(),
+            report_databases: config
+                .report
+                .databases
+                .iter()
+                .map(|(kind, database_id)| (*kind, database_id.trim().to_string()))
+                .collect(),
+            diary_schema,
+            report_schema,
+            report_period_property: config
+                .report
+                .period_property
+                .as_deref()
+                .map(str::trim)
+                .filter(|name| !name.is_empty())
+                .map(str::to_string),
+            max_block_depth: config.notion.max_block_depth,
+            fetch_concurrency: config.notion.fetch_concurrency,
+            retry_policy: RetryPolicy::default().with_max_attempts(config.http.max_retry_attempts),
+            rate_limiter: Arc::new(RateLimiter::new(
+                requests_per_second,
+                requests_per_second.ceil() as u32,
+            )),
         })
     }
+
+    /// 期間のレポートを書き込むデータベース
+    pub fn report_db_id_for(&self, kind: ReportPeriodKind) -> &str {
+        self.report_databases
+            .get(&kind)
+            .unwrap_or(&self.report_db_id)
+    }
 }
 
 #[derive(Clone)]
 pub struct GeminiService {
     pub client: Client,
     pub api_key: String,
+    pub retry_policy: RetryPolicy,
 }
 
 impl GeminiService {
-    pub fn new(client: Client, api_key: String) -> Result<Self, Box<dyn std::error::Error>> {
+    pub fn new(config: &Config) -> AppResult<Self> {
+        let client = Client::builder()
+            .timeout(Duration::from_secs(config.gemini.timeout_seconds))
+            .build()?;
         Ok(Self {
             client,
-            api_key: api_key.trim().to_string(),
+            api_key: config.gemini.api_key.trim().to_string(),
+            retry_policy: RetryPolicy::default().with_max_attempts(config.http.max_retry_attempts),
         })
     }
-}
\ No newline at end of file
+}
+
+#[derive(Clone)]
+pub struct OpenAiCompatibleService {
+    pub client: Client,
+    // 末尾の / を除いたベース URL (例: https://api.openai.com/v1)
+    pub base_url: String,
+    pub api_key: Option<String>,
+    pub retry_policy: RetryPolicy,
+}
+
+impl OpenAiCompatibleService {
+    pub fn new(config: &Config) -> AppResult<Self> {
+        let client = Client::builder()
+            .timeout(Duration::from_secs(config.openai.timeout_seconds))
+            .build()?;
+        Ok(Self {
+            client,
+            base_url: config
+                .openai
+                .base_url
+                .trim()
+                .trim_end_matches('/')
+                .to_string(),
+            api_key: config
+                .openai
+                .api_key
+                .as_deref()
+                .map(str::trim)
+                .filter(|api_key| !api_key.is_empty())
+                .map(str::to_string),
+            retry_policy: RetryPolicy::default().with_max_attempts(config.http.max_retry_attempts),
+        })
+    }
+}
+
+#[derive(Clone)]
+pub struct OllamaService {
+    pub client: Client,
+    pub base_url: String,
+    pub retry_policy: RetryPolicy,
+}
+
+impl OllamaService {
+    pub fn new(config: &Config) -> AppResult<Self> {
+        let client = Client::builder()
+            .timeout(Duration::from_secs(config.ollama.timeout_seconds))
+            .build()?;
+        Ok(Self {
+            client,
+            base_url: config
+                .ollama
+                .base_url
+                .trim()
+                .trim_end_matches('/')
+                .to_string(),
+            retry_policy: RetryPolicy::default().with_max_attempts(config.http.max_retry_attempts),
+        })
+    }
+}
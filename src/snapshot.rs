@@ -0,0 +1,151 @@
+pub mod handlers;
+
+use chrono::{DateTime, Utc};
+use rusqlite::{params, OptionalExtension, Row};
+use serde::Serialize;
+use serde_json::Value;
+
+use crate::{
+    api::append_notion_block_to_page,
+    error::AppResult,
+    jobs::{normalize_page_id, parse_json_column},
+    llm::repair::parse_block,
+    service::NotionService,
+    store::Store,
+};
+
+/// 削除する前に退避したブロック。Notion から取得した JSON を子ブロックごとそのまま持つ
+#[derive(Debug, Clone, Serialize)]
+pub struct BlockSnapshot {
+    pub id: i64,
+    pub page_id: String,
+    pub created_at: DateTime<Utc>,
+    pub blocks: Vec<Value>,
+}
+
+impl BlockSnapshot {
+    fn from_row(row: &Row) -> rusqlite::Result<Self> {
+        let blocks: String = row.get("blocks")?;
+        Ok(Self {
+            id: row.get("id")?,
+            page_id: row.get("page_id")?,
+            created_at: row.get("created_at")?,
+            blocks: parse_json_column(&blocks)?,
+        })
+    }
+}
+
+#[derive(Clone)]
+pub struct SnapshotStore {
+    store: Store,
+}
+
+impl SnapshotStore {
+    pub fn new(store: Store) -> Self {
+        Self { store }
+    }
+
+    pub fn save(&self, page_id: &str, blocks: &[Value]) -> AppResult<BlockSnapshot> {
+        let connection = self.store.connection();
+        connection.execute(
+            "INSERT INTO block_snapshots (page_id, blocks, created_at) VALUES (?1, ?2, ?3)",
+            params![
+                normalize_page_id(page_id),
+                serde_json::to_string(blocks)?,
+                Utc::now()
+            ],
+        )?;
+        Ok(connection.query_row(
+            "SELECT * FROM block_snapshots WHERE id = ?1",
+            [connection.last_insert_rowid()],
+            BlockSnapshot::from_row,
+        )?)
+    }
+
+    /// ページの退避履歴を新しい順に返す
+    pub fn list(&self, page_id: &str) -> AppResult<Vec<BlockSnapshot>> {
+        let connection = self.store.connection();
+        let mut statement = connection.prepare(
+            "SELECT * FROM block_snapshots WHERE page_id = ?1 ORDER BY created_at DESC, id DESC",
+        )?;
+        let snapshots = statement
+            .query_map([normalize_page_id(page_id)], BlockSnapshot::from_row)?
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(snapshots)
+    }
+
+    /// 指定時刻以前で最も新しい退避を返す。時刻を省略した場合は最新のもの
+    pub fn find(
+        &self,
+        page_id: &str,
+        at: Option<DateTime<Utc>>,
+    ) -> AppResult<Option<BlockSnapshot>> {
+        Ok(self
+            .store
+            .connection()
+            .query_row(
+                "SELECT * FROM block_snapshots WHERE page_id = ?1 AND created_at <= ?2
+                 ORDER BY created_at DESC, id DESC LIMIT 1",
+                params![normalize_page_id(page_id), at.unwrap_or_else(Utc::now)],
+                BlockSnapshot::from_row,
+            )
+            .optional()?)
+    }
+}
+
+/// 退避したブロックをページ末尾に追加し直す。読み取り専用の項目や未対応のブロックは落とす
+pub async fn restore_snapshot(
+    service: &NotionService,
+    page_id: &str,
+    snapshot: &BlockSnapshot,
+) -> AppResult<Vec<String>> {
+    let blocks = snapshot
+        .blocks
+        .iter()
+        .filter_map(|block| parse_block(block.clone()).ok())
+        .collect();
+    let appended_block_ids = append_notion_block_to_page(service, page_id, blocks).await?;
+    println!(
+        "Restored snapshot {} ({}) to page {}",
+        snapshot.id, snapshot.created_at, page_id
+    );
+    Ok(appended_block_ids)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::Duration;
+    use serde_json::json;
+
+    #[test]
+    fn test_save_and_find_snapshots() {
+        let snapshots = SnapshotStore::new(Store::open_in_memory().unwrap());
+        let first = snapshots
+            .save("Page-1", &[json!({"type": "paragraph", "id": "a"})])
+            .unwrap();
+        let second = snapshots
+            .save("page1", &[json!({"type": "callout", "id": "b"})])
+            .unwrap();
+
+        assert_eq!(first.page_id, "page1");
+        assert_eq!(snapshots.list("page-1").unwrap().len(), 2);
+        assert_eq!(
+            snapshots.find("page-1", None).unwrap().unwrap().id,
+            second.id
+        );
+        assert_eq!(
+            snapshots
+                .find("page-1", Some(first.created_at))
+                .unwrap()
+                .unwrap()
+                .blocks[0]["id"],
+            "a"
+        );
+        assert!(snapshots
+            .find("page-1", Some(first.created_at - Duration::seconds(1)))
+            .unwrap()
+            .is_none());
+        assert!(snapshots.find("other", None).unwrap().is_none());
+    }
+}
@@ -0,0 +1,63 @@
+use axum::{
+    extract::{Path, Query, State},
+    Json,
+};
+use chrono::{DateTime, Utc};
+use reqwest::StatusCode;
+use serde::Deserialize;
+use serde_json::{json, Value};
+
+use super::{restore_snapshot, BlockSnapshot};
+use crate::router::AppState;
+
+#[derive(Debug, Deserialize)]
+pub struct RestoreQuery {
+    // この時刻以前で最も新しい退避を戻す。省略時は最新
+    pub at: Option<DateTime<Utc>>,
+}
+
+pub async fn list_snapshots(
+    State(state): State<AppState>,
+    Path(page_id): Path<String>,
+) -> Result<Json<Vec<BlockSnapshot>>, (StatusCode, Json<Value>)> {
+    state.snapshots.list(&page_id).map(Json).map_err(|e| {
+        (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(json!({ "error": e.to_string() })),
+        )
+    })
+}
+
+pub async fn restore_page_snapshot(
+    State(state): State<AppState>,
+    Path(page_id): Path<String>,
+    Query(query): Query<RestoreQuery>,
+) -> Result<Json<Value>, (StatusCode, Json<Value>)> {
+    let snapshot = match state.snapshots.find(&page_id, query.at) {
+        Ok(Some(snapshot)) => snapshot,
+        Ok(None) => {
+            return Err((
+                StatusCode::NOT_FOUND,
+                Json(json!({ "error": format!("no snapshot for page {}", page_id) })),
+            ))
+        }
+        Err(e) => {
+            return Err((
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(json!({ "error": e.to_string() })),
+            ))
+        }
+    };
+
+    match restore_snapshot(&state.notion_service, &page_id, &snapshot).await {
+        Ok(appended_block_ids) => Ok(Json(json!({
+            "snapshot_id": snapshot.id,
+            "created_at": snapshot.created_at,
+            "appended_block_ids": appended_block_ids,
+        }))),
+        Err(e) => Err((
+            StatusCode::BAD_GATEWAY,
+            Json(json!({ "error": e.to_string() })),
+        )),
+    }
+}
@@ -0,0 +1,107 @@
+use std::{
+    path::Path,
+    sync::{Arc, Mutex, MutexGuard},
+};
+
+use rusqlite::Connection;
+
+use crate::error::AppResult;
+
+// PRAGMA user_version で適用済みのバージョンを管理する。既存の要素は変更せず末尾に追加すること
+const MIGRATIONS: &[&str] = &[
+    "CREATE TABLE jobs (
+        id INTEGER PRIMARY KEY AUTOINCREMENT,
+        kind TEXT NOT NULL,
+        payload TEXT NOT NULL,
+        state TEXT NOT NULL,
+        attempts INTEGER NOT NULL DEFAULT 0,
+        max_attempts INTEGER NOT NULL,
+        last_error TEXT,
+        run_after TEXT NOT NULL,
+        created_at TEXT NOT NULL,
+        updated_at TEXT NOT NULL
+    );
+    CREATE INDEX jobs_state_run_after ON jobs (state, run_after);",
+    "ALTER TABLE jobs ADD COLUMN page_id TEXT;
+    ALTER TABLE jobs ADD COLUMN model TEXT;
+    ALTER TABLE jobs ADD COLUMN appended_block_ids TEXT;
+    ALTER TABLE jobs ADD COLUMN started_at TEXT;
+    ALTER TABLE jobs ADD COLUMN finished_at TEXT;
+    CREATE INDEX jobs_page_id ON jobs (page_id, id);",
+    "CREATE TABLE dedup_keys (
+        key TEXT PRIMARY KEY,
+        job_id INTEGER NOT NULL,
+        created_at TEXT NOT NULL
+    );
+    CREATE INDEX dedup_keys_created_at ON dedup_keys (created_at);",
+    "CREATE TABLE block_snapshots (
+        id INTEGER PRIMARY KEY AUTOINCREMENT,
+        page_id TEXT NOT NULL,
+        blocks TEXT NOT NULL,
+        created_at TEXT NOT NULL
+    );
+    CREATE INDEX block_snapshots_page_id ON block_snapshots (page_id, created_at);",
+    "CREATE TABLE schedule_runs (
+        name TEXT PRIMARY KEY,
+        last_run_at TEXT NOT NULL
+    );",
+    "ALTER TABLE jobs ADD COLUMN failed_page_ids TEXT;",
+];
+
+/// ジョブキューや重複排除の記録、削除前のブロックの退避など、プロセスをまたいで残すローカル状態の SQLite ストア
+#[derive(Clone)]
+pub struct Store {
+    // rusqlite の接続は Sync ではないため Mutex で共有する。クエリは短いので同期で実行する
+    connection: Arc<Mutex<Connection>>,
+}
+
+impl Store {
+    pub fn open(path: impl AsRef<Path>) -> AppResult<Self> {
+        Self::from_connection(Connection::open(path)?)
+    }
+
+    pub fn open_in_memory() -> AppResult<Self> {
+        Self::from_connection(Connection::open_in_memory()?)
+    }
+
+    fn from_connection(mut connection: Connection) -> AppResult<Self> {
+        migrate(&mut connection)?;
+        Ok(Self {
+            connection: Arc::new(Mutex::new(connection)),
+        })
+    }
+
+    pub(crate) fn connection(&self) -> MutexGuard<'_, Connection> {
+        // 他のスレッドがパニックしても接続自体は使えるので、poison は無視する
+        self.connection
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+}
+
+fn migrate(connection: &mut Connection) -> AppResult<()> {
+    let version: i64 = connection.query_row("PRAGMA user_version", [], |row| row.get(0))?;
+    let transaction = connection.transaction()?;
+    for (index, migration) in MIGRATIONS.iter().enumerate().skip(version as usize) {
+        transaction.execute_batch(migration)?;
+        transaction.pragma_update(None, "user_version", index as i64 + 1)?;
+    }
+    transaction.commit()?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_migrations_are_idempotent() {
+        let mut connection = Connection::open_in_memory().unwrap();
+        migrate(&mut connection).unwrap();
+        migrate(&mut connection).unwrap();
+        let version: i64 = connection
+            .query_row("PRAGMA user_version", [], |row| row.get(0))
+            .unwrap();
+        assert_eq!(version as usize, MIGRATIONS.len());
+    }
+}
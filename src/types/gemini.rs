@@ -1,15 +1,27 @@
 use serde::{Deserialize, Serialize};
 
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub enum GeminiAPIModel {
     Gemini3Flash,
     Gemini3Pro,
+    // 設定ファイルで指定されたその他のモデル
+    Custom(String),
 }
 
 impl GeminiAPIModel {
-    pub fn model_name(&self) -> &'static str {
+    pub fn from_name(name: &str) -> Self {
+        match name.trim() {
+            "gemini-3-flash-preview" => Self::Gemini3Flash,
+            "gemini-3-pro-preview" => Self::Gemini3Pro,
+            other => Self::Custom(other.to_string()),
+        }
+    }
+
+    pub fn model_name(&self) -> &str {
         match &self {
             Self::Gemini3Flash => "gemini-3-flash-preview",
             Self::Gemini3Pro => "gemini-3-pro-preview",
+            Self::Custom(name) => name,
         }
     }
 }
@@ -46,6 +58,9 @@ pub struct Part {
 pub struct GenerationConfig {
     pub temperature: f32,
     pub response_mime_type: ResponseMimeType,
+    // response_mime_type が JSON のときに出力の形を制約する
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub response_schema: Option<serde_json::Value>,
 }
 
 impl Default for GenerationConfig {
@@ -53,6 +68,7 @@ impl Default for GenerationConfig {
         Self {
             temperature: 0.8,
             response_mime_type: ResponseMimeType::Json,
+            response_schema: None,
         }
     }
 }
@@ -69,13 +85,33 @@ pub enum ResponseMimeType {
 #[derive(Debug, Deserialize, Serialize)]
 #[serde(rename_all = "camelCase")]
 pub struct GeminiAPIResponse {
+    #[serde(default)]
     pub candidates: Vec<GeminiAPICandidate>,
+    pub prompt_feedback: Option<GeminiAPIPromptFeedback>,
 }
 
 #[derive(Debug, Deserialize, Serialize)]
 #[serde(rename_all = "camelCase")]
 pub struct GeminiAPICandidate {
-    pub content: GeminiAPIChatContent,
+    // セーフティでブロックされた場合は content が返らない
+    pub content: Option<GeminiAPIChatContent>,
+    pub finish_reason: Option<String>,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct GeminiAPIPromptFeedback {
+    pub block_reason: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct GeminiAPIErrorResponse {
+    pub error: GeminiAPIErrorDetail,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct GeminiAPIErrorDetail {
+    pub message: String,
 }
 
 #[cfg(test)]
@@ -1,8 +1,45 @@
+use std::collections::HashMap;
+
 use serde::{Deserialize, Serialize};
 
+use crate::period::ReportPeriodRequest;
+
+mod chunk;
+mod markdown;
+mod response_schema;
+
+pub use chunk::{chunk_blocks, DetachedChildren, MAX_CHILDREN_PER_REQUEST, MAX_RICH_TEXT_LENGTH};
+pub use markdown::{blocks_to_markdown, markdown_to_blocks};
+
 #[derive(Debug, Deserialize, Serialize)]
 pub struct NotionWebhookPayload {
+    // イベント配信の ID (Webhook サブスクリプション経由の場合)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub id: Option<String>,
+    // データベース自動化から送られた場合の送信元情報
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub source: Option<NotionWebhookSource>,
     pub data: NotionPageRef,
+    // 定期レポートの対象期間 (スケジューラや手動の呼び出しで指定する)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub period: Option<ReportPeriodRequest>,
+}
+
+impl NotionWebhookPayload {
+    /// 再送されても変わらないイベントの ID
+    pub fn event_id(&self) -> Option<&str> {
+        self.id
+            .as_deref()
+            .or_else(|| self.source.as_ref()?.event_id.as_deref())
+    }
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct NotionWebhookSource {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub automation_id: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub event_id: Option<String>,
 }
 
 #[derive(Debug, Deserialize, Serialize)]
@@ -10,17 +47,38 @@ pub struct NotionPageRef {
     pub id: String,
 }
 
-#[derive(Debug, Serialize)]
+// Webhook サブスクリプション作成時に一度だけ届く検証リクエスト
+#[derive(Debug, Deserialize)]
+pub struct NotionVerificationRequest {
+    pub verification_token: String,
+}
+
+// Notion API がエラー時に返すボディ
+#[derive(Debug, Deserialize)]
+pub struct NotionErrorResponse {
+    pub code: String,
+    pub message: String,
+}
+
+#[derive(Debug, Serialize, Clone, Default)]
 pub struct NotionDatabaseQuery {
     #[serde(skip_serializing_if = "Option::is_none")]
     pub filter: Option<serde_json::Value>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub sorts: Option<Vec<serde_json::Value>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub start_cursor: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub page_size: Option<u32>,
 }
 
 #[derive(Debug, Deserialize)]
 pub struct NotionDatabaseQueryResponse {
     pub results: Vec<NotionPage>,
+    #[serde(default)]
+    pub has_more: bool,
+    #[serde(default)]
+    pub next_cursor: Option<String>,
 }
 
 #[derive(Debug, Deserialize, Serialize)]
@@ -30,6 +88,19 @@ pub struct NotionPage {
     pub url: String,
 }
 
+#[derive(Debug, Deserialize)]
+pub struct NotionDatabase {
+    pub id: String,
+    // プロパティ名 → プロパティ定義
+    pub properties: HashMap<String, NotionPropertySchema>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct NotionPropertySchema {
+    #[serde(rename = "type")]
+    pub property_type: String,
+}
+
 #[derive(Debug, Serialize)]
 pub struct NotionCreatePageRequest {
     pub parent: Parent,
@@ -58,11 +129,21 @@ pub struct NotionAppendBlockRequest {
 pub enum AppendPositionType {
     Start,
     End,
+    AfterBlock { after_block: NotionBlockReference },
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct NotionBlockReference {
+    pub id: String,
 }
 
 #[derive(Debug, Deserialize, Serialize)]
 pub struct NotionBlockResponse {
     pub results: Vec<NotionBlock>,
+    #[serde(default)]
+    pub has_more: bool,
+    #[serde(default)]
+    pub next_cursor: Option<String>,
 }
 
 #[derive(Debug, Deserialize)]
@@ -72,9 +153,79 @@ pub struct NotionBlockId {
     pub block_type: String,
 }
 
+// 子ブロックを再帰的に組み立てるため、生の JSON のまま受け取るレスポンス
+#[derive(Debug, Deserialize)]
+pub struct NotionBlockValueListResponse {
+    pub results: Vec<serde_json::Value>,
+    #[serde(default)]
+    pub has_more: bool,
+    #[serde(default)]
+    pub next_cursor: Option<String>,
+}
+
 #[derive(Debug, Deserialize)]
 pub struct NotionBlockIdListResponse {
     pub results: Vec<NotionBlockId>,
+    #[serde(default)]
+    pub has_more: bool,
+    #[serde(default)]
+    pub next_cursor: Option<String>,
+}
+
+// Notion のリスト系エンドポイントは has_more / next_cursor でページングする
+pub trait NotionPaginated {
+    type Item;
+
+    fn next_cursor(&self) -> Option<&str>;
+    fn into_results(self) -> Vec<Self::Item>;
+}
+
+impl NotionPaginated for NotionBlockResponse {
+    type Item = NotionBlock;
+
+    fn next_cursor(&self) -> Option<&str> {
+        self.next_cursor.as_deref().filter(|_| self.has_more)
+    }
+
+    fn into_results(self) -> Vec<NotionBlock> {
+        self.results
+    }
+}
+
+impl NotionPaginated for NotionBlockIdListResponse {
+    type Item = NotionBlockId;
+
+    fn next_cursor(&self) -> Option<&str> {
+        self.next_cursor.as_deref().filter(|_| self.has_more)
+    }
+
+    fn into_results(self) -> Vec<NotionBlockId> {
+        self.results
+    }
+}
+
+impl NotionPaginated for NotionBlockValueListResponse {
+    type Item = serde_json::Value;
+
+    fn next_cursor(&self) -> Option<&str> {
+        self.next_cursor.as_deref().filter(|_| self.has_more)
+    }
+
+    fn into_results(self) -> Vec<serde_json::Value> {
+        self.results
+    }
+}
+
+impl NotionPaginated for NotionDatabaseQueryResponse {
+    type Item = NotionPage;
+
+    fn next_cursor(&self) -> Option<&str> {
+        self.next_cursor.as_deref().filter(|_| self.has_more)
+    }
+
+    fn into_results(self) -> Vec<NotionPage> {
+        self.results
+    }
 }
 
 #[derive(Debug, Deserialize, Serialize, Clone)]
@@ -119,6 +270,12 @@ pub enum NotionBlock {
     Code {
         code: CodeBlockContent,
     },
+    ColumnList {
+        column_list: ContainerBlockContent,
+    },
+    Column {
+        column: ContainerBlockContent,
+    },
     #[serde(other)]
     Unsupported,
 }
@@ -149,21 +306,64 @@ impl NotionBlock {
             code: CodeBlockContent::new(text, language),
         }
     }
+    pub fn callout(text: &str, children: Vec<NotionBlock>) -> Self {
+        Self::Callout {
+            callout: BlockContent {
+                rich_text: vec![NotionRichText::new(text)],
+                children: (!children.is_empty()).then_some(children),
+            },
+        }
+    }
+
+    pub fn children(&self) -> Option<&[NotionBlock]> {
+        let children = match self {
+            NotionBlock::Heading1 { heading_1 } => &heading_1.children,
+            NotionBlock::Heading2 { heading_2 } => &heading_2.children,
+            NotionBlock::Heading3 { heading_3 } => &heading_3.children,
+            NotionBlock::Paragraph { paragraph } => &paragraph.children,
+            NotionBlock::BulletedListItem { bulleted_list_item } => {
+                &bulleted_list_item.children
+            }
+            NotionBlock::NumberedListItem { numbered_list_item } => {
+                &numbered_list_item.children
+            }
+            NotionBlock::ToDo { to_do } => &to_do.children,
+            NotionBlock::Toggle { toggle } => &toggle.children,
+            NotionBlock::Quote { quote } => &quote.children,
+            NotionBlock::Callout { callout } => &callout.children,
+            NotionBlock::ColumnList { column_list } => &column_list.children,
+            NotionBlock::Column { column } => &column.children,
+            NotionBlock::Divider { .. } | NotionBlock::Code { .. } | NotionBlock::Unsupported => {
+                return None
+            }
+        };
+        children.as_deref()
+    }
 }
 
 #[derive(Debug, Deserialize, Serialize, Clone)]
 pub struct BlockContent {
     pub rich_text: Vec<NotionRichText>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub children: Option<Vec<NotionBlock>>,
 }
 
 impl BlockContent {
     fn new(text: &str) -> Self {
         Self {
             rich_text: vec![NotionRichText::new(text)],
+            children: None,
         }
     }
 }
 
+// カラムなど、テキストを持たず子ブロックだけを持つブロック用
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct ContainerBlockContent {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub children: Option<Vec<NotionBlock>>,
+}
+
 #[derive(Debug, Deserialize, Serialize, Clone)]
 pub struct CodeBlockContent {
     // caption
@@ -234,7 +434,7 @@ impl NotionRichText {
     }
 }
 
-#[derive(Debug, Deserialize, Serialize, Clone)]
+#[derive(Debug, Deserialize, Serialize, Clone, Default)]
 #[serde(rename_all = "snake_case")]
 #[serde(default)]
 pub struct RichTextAnnotations {
@@ -247,32 +447,28 @@ pub struct RichTextAnnotations {
     color: Option<RichTextColor>,
 }
 
-impl Default for RichTextAnnotations {
-    fn default() -> Self {
-        Self {
-            bold: false,
-            italic: false,
-            strikethrough: false,
-            underline: false,
-            code: false,
-            color: None,
-        }
-    }
-}
-
 #[derive(Debug, Deserialize, Serialize, Clone)]
 #[serde(rename_all = "snake_case")]
 pub enum RichTextColor {
     Default,
-    Red,
-    Blue,
-    Pink,
-    Green,
+    Gray,
+    Brown,
+    Orange,
     Yellow,
+    Green,
+    Blue,
     Purple,
-    Glay,
-    Blown,
-    Orange,
+    Pink,
+    Red,
+    GrayBackground,
+    BrownBackground,
+    OrangeBackground,
+    YellowBackground,
+    GreenBackground,
+    BlueBackground,
+    PurpleBackground,
+    PinkBackground,
+    RedBackground,
 }
 
 // テキスト抽出用トレイト
@@ -290,7 +486,6 @@ impl<T: HasRichText> ExtractText for T {
             rich_text
                 .iter()
                 .filter_map(|t| t.plain_text())
-                .map(|t| t)
                 .collect::<Vec<_>>()
                 .join(""),
         )
@@ -298,8 +493,9 @@ impl<T: HasRichText> ExtractText for T {
 }
 
 impl ExtractText for NotionBlock {
+    // 自身のテキストに続けて、子ブロックのテキストを再帰的に連結する
     fn extract_text(&self) -> Option<String> {
-        match self {
+        let own_text = match self {
             NotionBlock::Heading1 { heading_1 } => heading_1.extract_text(),
             NotionBlock::Heading2 { heading_2 } => heading_2.extract_text(),
             NotionBlock::Heading3 { heading_3 } => heading_3.extract_text(),
@@ -316,7 +512,24 @@ impl ExtractText for NotionBlock {
             NotionBlock::Callout { callout } => callout.extract_text(),
             NotionBlock::Divider { .. } => None,
             NotionBlock::Code { code } => code.extract_text(),
+            NotionBlock::ColumnList { .. } | NotionBlock::Column { .. } => None,
             NotionBlock::Unsupported => None,
+        };
+
+        let texts: Vec<String> = own_text
+            .into_iter()
+            .chain(
+                self.children()
+                    .unwrap_or_default()
+                    .iter()
+                    .filter_map(|child| child.extract_text()),
+            )
+            .collect();
+
+        if texts.is_empty() {
+            None
+        } else {
+            Some(texts.join("\n"))
         }
     }
 }
@@ -353,6 +566,8 @@ impl HasRichText for CodeBlockContent {
 pub struct ToDoBlockContent {
     pub rich_text: Vec<NotionRichText>,
     pub checked: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub children: Option<Vec<NotionBlock>>,
 }
 
 #[derive(Debug, Deserialize, Serialize, Clone)]
@@ -384,6 +599,7 @@ impl NotionTextContent {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use serde_json::json;
 
     #[test]
     fn test_extract_text_paragraph() {
@@ -407,11 +623,95 @@ mod tests {
             to_do: ToDoBlockContent {
                 rich_text: vec![NotionRichText::new("Buy milk")],
                 checked: false,
+                children: None,
             },
         };
         assert_eq!(block.extract_text(), Some("Buy milk".to_string()));
     }
 
+    #[test]
+    fn test_extract_text_walks_nested_children() {
+        let json = r#"{
+            "type": "toggle",
+            "toggle": {
+                "rich_text": [{"type": "text", "text": {"content": "Morning"}, "plain_text": "Morning"}],
+                "children": [
+                    {
+                        "type": "bulleted_list_item",
+                        "bulleted_list_item": {
+                            "rich_text": [{"type": "text", "text": {"content": "Run"}}],
+                            "children": [
+                                {"type": "paragraph", "paragraph": {"rich_text": [{"type": "text", "text": {"content": "5km"}}]}}
+                            ]
+                        }
+                    },
+                    {"type": "divider", "divider": {}}
+                ]
+            }
+        }"#;
+        let block: NotionBlock = serde_json::from_str(json).unwrap();
+        assert_eq!(block.children().map(|c| c.len()), Some(2));
+        assert_eq!(block.extract_text(), Some("Morning\nRun\n5km".to_string()));
+    }
+
+    #[test]
+    fn test_extract_text_columns() {
+        let json = r#"{
+            "type": "column_list",
+            "column_list": {
+                "children": [
+                    {"type": "column", "column": {"children": [
+                        {"type": "paragraph", "paragraph": {"rich_text": [{"type": "text", "text": {"content": "Left"}}]}}
+                    ]}},
+                    {"type": "column", "column": {"children": [
+                        {"type": "paragraph", "paragraph": {"rich_text": [{"type": "text", "text": {"content": "Right"}}]}}
+                    ]}}
+                ]
+            }
+        }"#;
+        let block: NotionBlock = serde_json::from_str(json).unwrap();
+        assert_eq!(block.extract_text(), Some("Left\nRight".to_string()));
+    }
+
+    #[test]
+    fn test_webhook_payload_event_id() {
+        let automation: NotionWebhookPayload = serde_json::from_str(
+            r#"{"source":{"type":"automation","automation_id":"a1","event_id":"e1","attempt":1},"data":{"object":"page","id":"p1"}}"#,
+        )
+        .unwrap();
+        assert_eq!(automation.event_id(), Some("e1"));
+
+        let button: NotionWebhookPayload =
+            serde_json::from_str(r#"{"data":{"id":"p1"}}"#).unwrap();
+        assert_eq!(button.event_id(), None);
+    }
+
+    #[test]
+    fn test_pagination_cursor() {
+        let response: NotionBlockIdListResponse = serde_json::from_str(
+            r#"{"results":[{"id":"a","type":"paragraph"}],"has_more":true,"next_cursor":"c1"}"#,
+        )
+        .unwrap();
+        assert_eq!(response.next_cursor(), Some("c1"));
+
+        let last_page: NotionBlockIdListResponse = serde_json::from_str(
+            r#"{"results":[],"has_more":false,"next_cursor":null}"#,
+        )
+        .unwrap();
+        assert_eq!(last_page.next_cursor(), None);
+        assert!(last_page.into_results().is_empty());
+    }
+
+    #[test]
+    fn test_database_query_serialization_with_cursor() {
+        let query = NotionDatabaseQuery {
+            start_cursor: Some("c1".to_string()),
+            ..Default::default()
+        };
+        let json = serde_json::to_string(&query).unwrap();
+        assert_eq!(json, r#"{"start_cursor":"c1"}"#);
+    }
+
     #[test]
     fn test_notion_block_serialization() {
         let block = NotionBlock::paragraph("Test");
@@ -419,4 +719,17 @@ mod tests {
         assert!(json.contains("\"type\":\"paragraph\""));
         assert!(json.contains("\"content\":\"Test\""));
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_all_notion_colors_deserialize() {
+        let block: NotionBlock = serde_json::from_value(json!({
+            "type": "paragraph",
+            "paragraph": {"rich_text": [
+                {"type": "text", "text": {"content": "a"}, "annotations": {"color": "gray"}},
+                {"type": "text", "text": {"content": "b"}, "annotations": {"color": "brown_background"}}
+            ]}
+        }))
+        .unwrap();
+        assert_eq!(block.extract_text().as_deref(), Some("ab"));
+    }
+}
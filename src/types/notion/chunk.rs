@@ -0,0 +1,325 @@
+use super::{NotionBlock, NotionRichText};
+
+// Notion の rich_text 1 要素あたりの最大文字数
+pub const MAX_RICH_TEXT_LENGTH: usize = 2000;
+// 1 回のリクエストで送れる children の最大件数
+pub const MAX_CHILDREN_PER_REQUEST: usize = 100;
+// 1 回のリクエストで送れる入れ子の深さ (トップレベルの下に 2 階層まで)
+pub const MAX_NESTING_DEPTH: usize = 2;
+
+/// 親ブロックを追加した後に、改めて追加する子ブロック
+#[derive(Debug)]
+pub enum DetachedChildren {
+    // 親ブロックの子として追加する
+    Children(Vec<NotionBlock>),
+    // 段組みの列ごとに、その列の末尾へ追加する (列と同じ順番)
+    Columns(Vec<Vec<NotionBlock>>),
+}
+
+impl NotionBlock {
+    /// 上限を超える長さのテキストを、同じ装飾・リンクのまま複数の要素に分ける
+    pub fn split_long_rich_text(mut self) -> Self {
+        if let Some(rich_text) = self.rich_text_mut() {
+            *rich_text = std::mem::take(rich_text)
+                .into_iter()
+                .flat_map(split_rich_text)
+                .collect();
+        }
+        if let Some(children) = self.children_mut().and_then(Option::as_mut) {
+            *children = std::mem::take(children)
+                .into_iter()
+                .map(NotionBlock::split_long_rich_text)
+                .collect();
+        }
+        self
+    }
+
+    /// 1 回のリクエストに収まらない子ブロックを取り外し、後から追加できるように返す。
+    /// 段組みは列を空にできないため、深すぎる部分を平坦化し、各列の上限を超えた分だけを外す
+    pub fn detach_oversized_children(mut self) -> (Self, Option<DetachedChildren>) {
+        if fits_in_request(&self, MAX_NESTING_DEPTH) {
+            return (self, None);
+        }
+        if let NotionBlock::ColumnList { column_list } = &mut self {
+            let mut columns = column_list
+                .children
+                .take()
+                .map(|columns| flatten_nesting(columns, MAX_NESTING_DEPTH - 1))
+                .unwrap_or_default();
+            let overflow: Vec<Vec<NotionBlock>> = columns
+                .iter_mut()
+                .map(|column| {
+                    column
+                        .children_mut()
+                        .and_then(Option::as_mut)
+                        .filter(|children| children.len() > MAX_CHILDREN_PER_REQUEST)
+                        .map(|children| children.split_off(MAX_CHILDREN_PER_REQUEST))
+                        .unwrap_or_default()
+                })
+                .collect();
+            column_list.children = Some(columns);
+            let detached = overflow
+                .iter()
+                .any(|blocks| !blocks.is_empty())
+                .then_some(DetachedChildren::Columns(overflow));
+            return (self, detached);
+        }
+        let children = self
+            .children_mut()
+            .and_then(Option::take)
+            .map(DetachedChildren::Children);
+        (self, children)
+    }
+
+    fn rich_text_mut(&mut self) -> Option<&mut Vec<NotionRichText>> {
+        match self {
+            NotionBlock::Heading1 { heading_1: content }
+            | NotionBlock::Heading2 { heading_2: content }
+            | NotionBlock::Heading3 { heading_3: content }
+            | NotionBlock::Paragraph { paragraph: content }
+            | NotionBlock::BulletedListItem {
+                bulleted_list_item: content,
+            }
+            | NotionBlock::NumberedListItem {
+                numbered_list_item: content,
+            }
+            | NotionBlock::Quote { quote: content }
+            | NotionBlock::Callout { callout: content } => Some(&mut content.rich_text),
+            NotionBlock::ToDo { to_do } => Some(&mut to_do.rich_text),
+            NotionBlock::Toggle { toggle } => Some(&mut toggle.rich_text),
+            NotionBlock::Code { code } => Some(&mut code.rich_text),
+            NotionBlock::Divider { .. }
+            | NotionBlock::ColumnList { .. }
+            | NotionBlock::Column { .. }
+            | NotionBlock::Unsupported => None,
+        }
+    }
+
+    fn children_mut(&mut self) -> Option<&mut Option<Vec<NotionBlock>>> {
+        match self {
+            NotionBlock::Heading1 { heading_1: content }
+            | NotionBlock::Heading2 { heading_2: content }
+            | NotionBlock::Heading3 { heading_3: content }
+            | NotionBlock::Paragraph { paragraph: content }
+            | NotionBlock::BulletedListItem {
+                bulleted_list_item: content,
+            }
+            | NotionBlock::NumberedListItem {
+                numbered_list_item: content,
+            }
+            | NotionBlock::Quote { quote: content }
+            | NotionBlock::Callout { callout: content } => Some(&mut content.children),
+            NotionBlock::ToDo { to_do } => Some(&mut to_do.children),
+            NotionBlock::Toggle { toggle } => Some(&mut toggle.children),
+            NotionBlock::ColumnList { column_list } => Some(&mut column_list.children),
+            NotionBlock::Column { column } => Some(&mut column.children),
+            NotionBlock::Divider { .. } | NotionBlock::Code { .. } | NotionBlock::Unsupported => {
+                None
+            }
+        }
+    }
+}
+
+/// ブロックを 1 回のリクエストで送れる件数ごとに分ける
+pub fn chunk_blocks(blocks: Vec<NotionBlock>) -> Vec<Vec<NotionBlock>> {
+    let mut chunks = vec![];
+    let mut blocks = blocks.into_iter().peekable();
+    while blocks.peek().is_some() {
+        chunks.push(blocks.by_ref().take(MAX_CHILDREN_PER_REQUEST).collect());
+    }
+    chunks
+}
+
+fn split_rich_text(segment: NotionRichText) -> Vec<NotionRichText> {
+    let NotionRichText::Text { text, .. } = &segment else {
+        return vec![segment];
+    };
+    if text.content.chars().count() <= MAX_RICH_TEXT_LENGTH {
+        return vec![segment];
+    }
+
+    let chars: Vec<char> = text.content.chars().collect();
+    chars
+        .chunks(MAX_RICH_TEXT_LENGTH)
+        .map(|chunk| {
+            let mut piece = segment.clone();
+            if let NotionRichText::Text {
+                text, plain_text, ..
+            } = &mut piece
+            {
+                text.content = chunk.iter().collect();
+                *plain_text = None;
+            }
+            piece
+        })
+        .collect()
+}
+
+// depth は残りの入れ子の深さ。children の件数と深さの両方が収まるかを見る
+fn fits_in_request(block: &NotionBlock, depth: usize) -> bool {
+    match block.children() {
+        None | Some([]) => true,
+        Some(children) => {
+            depth > 0
+                && children.len() <= MAX_CHILDREN_PER_REQUEST
+                && children
+                    .iter()
+                    .all(|child| fits_in_request(child, depth - 1))
+        }
+    }
+}
+
+// depth より深い子孫を、親の直後に兄弟として並べ直す
+fn flatten_nesting(blocks: Vec<NotionBlock>, depth: usize) -> Vec<NotionBlock> {
+    let mut flattened = vec![];
+    for mut block in blocks {
+        let children = block.children_mut().and_then(Option::take);
+        match children {
+            Some(children) if depth == 0 => {
+                flattened.push(block);
+                flattened.extend(flatten_nesting(children, 0));
+            }
+            Some(children) => {
+                if let Some(slot) = block.children_mut() {
+                    *slot = Some(flatten_nesting(children, depth - 1));
+                }
+                flattened.push(block);
+            }
+            None => flattened.push(block),
+        }
+    }
+    flattened
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::ExtractText;
+    use serde_json::{json, Value};
+
+    fn nested_list(depth: usize) -> Value {
+        let mut block =
+            json!({"type": "bulleted_list_item", "bulleted_list_item": {"rich_text": []}});
+        for _ in 0..depth {
+            block = json!({
+                "type": "bulleted_list_item",
+                "bulleted_list_item": {"rich_text": [], "children": [block]}
+            });
+        }
+        block
+    }
+
+    #[test]
+    fn test_split_long_rich_text_keeps_annotations() {
+        let block: NotionBlock = serde_json::from_value(json!({
+            "type": "toggle",
+            "toggle": {
+                "rich_text": [],
+                "children": [{
+                    "type": "paragraph",
+                    "paragraph": {"rich_text": [{
+                        "type": "text",
+                        "text": {"content": "あ".repeat(MAX_RICH_TEXT_LENGTH * 2 + 1)},
+                        "annotations": {"bold": true}
+                    }]}
+                }]
+            }
+        }))
+        .unwrap();
+
+        let json = serde_json::to_value(block.split_long_rich_text()).unwrap();
+        let rich_text = &json["toggle"]["children"][0]["paragraph"]["rich_text"];
+        assert_eq!(rich_text.as_array().unwrap().len(), 3);
+        assert_eq!(rich_text[2]["text"]["content"], "あ");
+        assert_eq!(rich_text[2]["annotations"]["bold"], true);
+    }
+
+    #[test]
+    fn test_chunk_blocks() {
+        let blocks = vec![NotionBlock::paragraph("x"); MAX_CHILDREN_PER_REQUEST * 2 + 1];
+        let chunks = chunk_blocks(blocks);
+        assert_eq!(
+            chunks.iter().map(Vec::len).collect::<Vec<_>>(),
+            vec![100, 100, 1]
+        );
+    }
+
+    #[test]
+    fn test_detach_oversized_children() {
+        let shallow: NotionBlock = serde_json::from_value(nested_list(2)).unwrap();
+        assert!(shallow.detach_oversized_children().1.is_none());
+
+        let deep: NotionBlock = serde_json::from_value(nested_list(3)).unwrap();
+        let (block, detached) = deep.detach_oversized_children();
+        assert!(block.children().is_none());
+        assert!(
+            matches!(detached, Some(DetachedChildren::Children(children)) if children.len() == 1)
+        );
+
+        let wide = NotionBlock::Toggle {
+            toggle: super::super::ToggleBlockContent {
+                rich_text: vec![],
+                children: Some(vec![NotionBlock::paragraph("x"); 101]),
+            },
+        };
+        assert!(matches!(
+            wide.detach_oversized_children().1,
+            Some(DetachedChildren::Children(children)) if children.len() == 101
+        ));
+    }
+
+    #[test]
+    fn test_column_list_is_flattened() {
+        let column_list: NotionBlock = serde_json::from_value(json!({
+            "type": "column_list",
+            "column_list": {"children": [
+                {"type": "column", "column": {"children": [nested_list(2)]}},
+                {"type": "column", "column": {"children": [{"type": "paragraph", "paragraph": {"rich_text": [{"type": "text", "text": {"content": "右"}}]}}]}}
+            ]}
+        }))
+        .unwrap();
+
+        let (block, detached) = column_list.detach_oversized_children();
+        assert!(detached.is_none());
+        let column = &block.children().unwrap()[0];
+        assert_eq!(column.children().unwrap().len(), 3);
+        assert!(column.children().unwrap()[0].children().is_none());
+        assert_eq!(
+            block.children().unwrap()[1].extract_text().as_deref(),
+            Some("右")
+        );
+    }
+
+    #[test]
+    fn test_wide_column_is_capped() {
+        let mut paragraphs =
+            vec![json!({"type": "paragraph", "paragraph": {"rich_text": []}}); 150];
+        paragraphs[0] = nested_list(2);
+        let column_list: NotionBlock = serde_json::from_value(json!({
+            "type": "column_list",
+            "column_list": {"children": [
+                {"type": "column", "column": {"children": [nested_list(0)]}},
+                {"type": "column", "column": {"children": paragraphs}}
+            ]}
+        }))
+        .unwrap();
+
+        let (block, detached) = column_list.detach_oversized_children();
+        assert!(fits_in_request(&block, MAX_NESTING_DEPTH));
+        let columns = block.children().unwrap();
+        assert_eq!(columns[0].children().unwrap().len(), 1);
+        assert_eq!(
+            columns[1].children().unwrap().len(),
+            MAX_CHILDREN_PER_REQUEST
+        );
+        match detached {
+            Some(DetachedChildren::Columns(overflow)) => {
+                assert_eq!(overflow.len(), 2);
+                assert!(overflow[0].is_empty());
+                // 平坦化で増えた 2 件を含む 152 件のうち、100 件を超えた分を後から追加する
+                assert_eq!(overflow[1].len(), 52);
+            }
+            other => panic!("unexpected detached children: {other:?}"),
+        }
+    }
+}
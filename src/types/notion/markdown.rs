@@ -0,0 +1,729 @@
+use pulldown_cmark::{CodeBlockKind, Event, HeadingLevel, Options, Parser, Tag, TagEnd};
+
+use super::{
+    BlockContent, CodeBlockContent, NotionBlock, NotionLinkText, NotionRichText, NotionTextContent,
+    RichTextAnnotations, ToDoBlockContent,
+};
+
+// Notion のコードブロックが受け付ける言語。生成時のスキーマでも同じ一覧を使う
+pub(super) const NOTION_CODE_LANGUAGES: &[&str] = &[
+    "abap",
+    "arduino",
+    "bash",
+    "basic",
+    "c",
+    "clojure",
+    "coffeescript",
+    "c++",
+    "c#",
+    "css",
+    "dart",
+    "diff",
+    "docker",
+    "elixir",
+    "elm",
+    "erlang",
+    "flow",
+    "fortran",
+    "f#",
+    "gherkin",
+    "glsl",
+    "go",
+    "graphql",
+    "groovy",
+    "haskell",
+    "html",
+    "java",
+    "javascript",
+    "json",
+    "julia",
+    "kotlin",
+    "latex",
+    "less",
+    "lisp",
+    "livescript",
+    "lua",
+    "makefile",
+    "markdown",
+    "markup",
+    "matlab",
+    "mermaid",
+    "nix",
+    "objective-c",
+    "ocaml",
+    "pascal",
+    "perl",
+    "php",
+    "plain text",
+    "powershell",
+    "prolog",
+    "protobuf",
+    "python",
+    "r",
+    "reason",
+    "ruby",
+    "rust",
+    "sass",
+    "scala",
+    "scheme",
+    "scss",
+    "shell",
+    "sql",
+    "swift",
+    "typescript",
+    "vb.net",
+    "verilog",
+    "vhdl",
+    "visual basic",
+    "webassembly",
+    "xml",
+    "yaml",
+];
+
+/// Markdown を Notion ブロックに変換する。
+/// 見出し・段落・入れ子のリスト・ToDo・引用・コード・区切り線と、太字などのインライン装飾に対応する
+pub fn markdown_to_blocks(markdown: &str) -> Vec<NotionBlock> {
+    let options = Options::ENABLE_TASKLISTS | Options::ENABLE_STRIKETHROUGH;
+    let mut converter = Converter::default();
+    for event in Parser::new_ext(markdown, options) {
+        converter.handle(event);
+    }
+    converter.finish()
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+struct InlineStyle {
+    bold: bool,
+    italic: bool,
+    strikethrough: bool,
+    code: bool,
+    link: Option<String>,
+}
+
+// 子ブロックを持てるコンテナ (リスト項目・引用)
+enum Frame {
+    Item {
+        ordered: bool,
+        checked: Option<bool>,
+        rich_text: Option<Vec<NotionRichText>>,
+        children: Vec<NotionBlock>,
+    },
+    Quote {
+        rich_text: Option<Vec<NotionRichText>>,
+        children: Vec<NotionBlock>,
+    },
+}
+
+#[derive(Default)]
+struct Converter {
+    blocks: Vec<NotionBlock>,
+    frames: Vec<Frame>,
+    // リストごとの番号付きかどうか
+    lists: Vec<bool>,
+    inline: Vec<(String, InlineStyle)>,
+    style: InlineStyle,
+    code_block: Option<(String, String)>,
+}
+
+impl Converter {
+    fn handle(&mut self, event: Event) {
+        if let Some((_, code)) = self.code_block.as_mut() {
+            match event {
+                Event::Text(text) => return code.push_str(&text),
+                Event::End(TagEnd::CodeBlock) => {}
+                _ => return,
+            }
+        }
+
+        match event {
+            Event::Start(tag) => self.start(tag),
+            Event::End(tag) => self.end(tag),
+            Event::Text(text) | Event::Html(text) | Event::InlineHtml(text) => {
+                self.push_text(&text, self.style.clone())
+            }
+            Event::Code(text) => {
+                let style = InlineStyle {
+                    code: true,
+                    ..self.style.clone()
+                };
+                self.push_text(&text, style);
+            }
+            Event::SoftBreak => self.push_text(" ", self.style.clone()),
+            Event::HardBreak => self.push_text("\n", self.style.clone()),
+            Event::Rule => {
+                self.flush_paragraph();
+                self.push_block(NotionBlock::Divider {
+                    divider: super::EmptyStruct {},
+                });
+            }
+            Event::TaskListMarker(checked) => {
+                if let Some(Frame::Item { checked: slot, .. }) = self.frames.last_mut() {
+                    *slot = Some(checked);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    fn start(&mut self, tag: Tag) {
+        match tag {
+            Tag::Strong => self.style.bold = true,
+            Tag::Emphasis => self.style.italic = true,
+            Tag::Strikethrough => self.style.strikethrough = true,
+            Tag::Link { dest_url, .. } => self.style.link = Some(dest_url.to_string()),
+            Tag::Paragraph | Tag::Image { .. } => {}
+            Tag::Heading { .. } => self.flush_paragraph(),
+            Tag::List(start) => {
+                self.flush_paragraph();
+                self.lists.push(start.is_some());
+            }
+            Tag::Item => {
+                self.flush_paragraph();
+                self.frames.push(Frame::Item {
+                    ordered: self.lists.last().copied().unwrap_or(false),
+                    checked: None,
+                    rich_text: None,
+                    children: Vec::new(),
+                });
+            }
+            Tag::BlockQuote(_) => {
+                self.flush_paragraph();
+                self.frames.push(Frame::Quote {
+                    rich_text: None,
+                    children: Vec::new(),
+                });
+            }
+            Tag::CodeBlock(kind) => {
+                self.flush_paragraph();
+                let language = match kind {
+                    CodeBlockKind::Fenced(info) => {
+                        info.split_whitespace().next().unwrap_or("").to_string()
+                    }
+                    CodeBlockKind::Indented => String::new(),
+                };
+                self.code_block = Some((language, String::new()));
+            }
+            _ => self.flush_paragraph(),
+        }
+    }
+
+    fn end(&mut self, tag: TagEnd) {
+        match tag {
+            TagEnd::Strong => self.style.bold = false,
+            TagEnd::Emphasis => self.style.italic = false,
+            TagEnd::Strikethrough => self.style.strikethrough = false,
+            TagEnd::Link => self.style.link = None,
+            TagEnd::Image => {}
+            TagEnd::Paragraph => self.flush_paragraph(),
+            TagEnd::Heading(level) => {
+                let content = BlockContent {
+                    rich_text: self.take_rich_text(),
+                    children: None,
+                };
+                self.push_block(match level {
+                    HeadingLevel::H1 => NotionBlock::Heading1 { heading_1: content },
+                    HeadingLevel::H2 => NotionBlock::Heading2 { heading_2: content },
+                    _ => NotionBlock::Heading3 { heading_3: content },
+                });
+            }
+            TagEnd::List(_) => {
+                self.lists.pop();
+            }
+            TagEnd::Item => {
+                self.flush_paragraph();
+                if let Some(Frame::Item {
+                    ordered,
+                    checked,
+                    rich_text,
+                    children,
+                }) = self.frames.pop()
+                {
+                    let rich_text = rich_text.unwrap_or_default();
+                    let children = Some(children).filter(|children| !children.is_empty());
+                    self.push_block(match (checked, ordered) {
+                        (Some(checked), _) => NotionBlock::ToDo {
+                            to_do: ToDoBlockContent {
+                                rich_text,
+                                checked,
+                                children,
+                            },
+                        },
+                        (None, true) => NotionBlock::NumberedListItem {
+                            numbered_list_item: BlockContent {
+                                rich_text,
+                                children,
+                            },
+                        },
+                        (None, false) => NotionBlock::BulletedListItem {
+                            bulleted_list_item: BlockContent {
+                                rich_text,
+                                children,
+                            },
+                        },
+                    });
+                }
+            }
+            TagEnd::BlockQuote(_) => {
+                self.flush_paragraph();
+                if let Some(Frame::Quote {
+                    rich_text,
+                    children,
+                }) = self.frames.pop()
+                {
+                    self.push_block(NotionBlock::Quote {
+                        quote: BlockContent {
+                            rich_text: rich_text.unwrap_or_default(),
+                            children: Some(children).filter(|children| !children.is_empty()),
+                        },
+                    });
+                }
+            }
+            TagEnd::CodeBlock => {
+                if let Some((language, code)) = self.code_block.take() {
+                    self.push_block(NotionBlock::Code {
+                        code: CodeBlockContent {
+                            rich_text: vec![NotionRichText::new(code.trim_end_matches('\n'))],
+                            language: notion_code_language(&language),
+                        },
+                    });
+                }
+            }
+            _ => self.flush_paragraph(),
+        }
+    }
+
+    fn push_text(&mut self, text: &str, style: InlineStyle) {
+        match self.inline.last_mut() {
+            Some((content, last_style)) if *last_style == style => content.push_str(text),
+            _ => self.inline.push((text.to_string(), style)),
+        }
+    }
+
+    fn take_rich_text(&mut self) -> Vec<NotionRichText> {
+        self.inline
+            .drain(..)
+            .map(|(content, style)| NotionRichText::Text {
+                text: NotionTextContent {
+                    content,
+                    link: style.link.map(|url| NotionLinkText { url }),
+                },
+                plain_text: None,
+                annotations: RichTextAnnotations {
+                    bold: style.bold,
+                    italic: style.italic,
+                    strikethrough: style.strikethrough,
+                    code: style.code,
+                    ..Default::default()
+                },
+            })
+            .collect()
+    }
+
+    // 溜まったインラインを段落にする。リスト項目や引用の最初の段落はその本文になる
+    fn flush_paragraph(&mut self) {
+        if self
+            .inline
+            .iter()
+            .all(|(content, _)| content.trim().is_empty())
+        {
+            self.inline.clear();
+            return;
+        }
+        let rich_text = self.take_rich_text();
+        match self.frames.last_mut() {
+            Some(Frame::Item {
+                rich_text: slot @ None,
+                ..
+            })
+            | Some(Frame::Quote {
+                rich_text: slot @ None,
+                ..
+            }) => *slot = Some(rich_text),
+            _ => self.push_block(NotionBlock::Paragraph {
+                paragraph: BlockContent {
+                    rich_text,
+                    children: None,
+                },
+            }),
+        }
+    }
+
+    fn push_block(&mut self, block: NotionBlock) {
+        match self.frames.last_mut() {
+            Some(Frame::Item { children, .. }) | Some(Frame::Quote { children, .. }) => {
+                children.push(block)
+            }
+            None => self.blocks.push(block),
+        }
+    }
+
+    fn finish(mut self) -> Vec<NotionBlock> {
+        self.flush_paragraph();
+        self.blocks
+    }
+}
+
+// Notion が知らない言語はエラーになるため、よく使う別名を寄せて残りは plain text にする
+fn notion_code_language(language: &str) -> String {
+    let language = language.trim().to_lowercase();
+    let language = match language.as_str() {
+        "rs" => "rust",
+        "js" | "jsx" | "mjs" => "javascript",
+        "ts" | "tsx" => "typescript",
+        "py" => "python",
+        "rb" => "ruby",
+        "sh" | "zsh" | "console" => "shell",
+        "yml" => "yaml",
+        "md" => "markdown",
+        "cpp" | "cc" | "cxx" => "c++",
+        "cs" | "csharp" => "c#",
+        "fs" | "fsharp" => "f#",
+        "kt" => "kotlin",
+        "golang" => "go",
+        "dockerfile" => "docker",
+        "ps1" | "pwsh" => "powershell",
+        "proto" => "protobuf",
+        "objc" => "objective-c",
+        "tex" => "latex",
+        other => other,
+    };
+    if NOTION_CODE_LANGUAGES.contains(&language) {
+        language.to_string()
+    } else {
+        "plain text".to_string()
+    }
+}
+
+/// Notion ブロックを Markdown に変換する。
+/// 見出し・リスト・ToDo の状態・コードの言語・リンクなどの構造を保ったまま、一つの文書として描画する
+pub fn blocks_to_markdown(blocks: &[NotionBlock]) -> String {
+    let mut lines = Vec::new();
+    render_blocks(blocks, "", &mut lines);
+    lines.join("\n")
+}
+
+// 兄弟ブロックを順に描画する。リスト項目が続く間は空行を挟まない
+fn render_blocks(blocks: &[NotionBlock], indent: &str, lines: &mut Vec<String>) {
+    let mut number = 0;
+    let mut previous_is_list = false;
+    for (index, block) in blocks
+        .iter()
+        .filter(|block| !matches!(block, NotionBlock::Unsupported))
+        .enumerate()
+    {
+        let is_list = is_list_item(block);
+        number = match block {
+            NotionBlock::NumberedListItem { .. } => number + 1,
+            _ => 0,
+        };
+        if index > 0 && !(is_list && previous_is_list) {
+            lines.push(indent.trim_end().to_string());
+        }
+        render_block(block, indent, number, lines);
+        previous_is_list = is_list;
+    }
+}
+
+fn render_block(block: &NotionBlock, indent: &str, number: usize, lines: &mut Vec<String>) {
+    // 続きの行と子ブロックは、リストならマーカーの幅だけ、引用なら "> " を付けて字下げする
+    let (marker, rich_text, child_indent) = match block {
+        NotionBlock::Heading1 { heading_1 } => ("# ".to_string(), &heading_1.rich_text, ""),
+        NotionBlock::Heading2 { heading_2 } => ("## ".to_string(), &heading_2.rich_text, ""),
+        NotionBlock::Heading3 { heading_3 } => ("### ".to_string(), &heading_3.rich_text, ""),
+        NotionBlock::Paragraph { paragraph } => (String::new(), &paragraph.rich_text, ""),
+        NotionBlock::BulletedListItem { bulleted_list_item } => {
+            ("- ".to_string(), &bulleted_list_item.rich_text, "  ")
+        }
+        NotionBlock::NumberedListItem { numbered_list_item } => {
+            let marker = format!("{number}. ");
+            let width = marker.len();
+            return render_text_block(
+                block,
+                &marker,
+                &numbered_list_item.rich_text,
+                indent,
+                &format!("{indent}{}", " ".repeat(width)),
+                lines,
+            );
+        }
+        NotionBlock::ToDo { to_do } => {
+            let check = if to_do.checked { "x" } else { " " };
+            (format!("- [{check}] "), &to_do.rich_text, "  ")
+        }
+        NotionBlock::Toggle { toggle } => ("- ".to_string(), &toggle.rich_text, "  "),
+        NotionBlock::Quote { quote } => ("> ".to_string(), &quote.rich_text, "> "),
+        NotionBlock::Callout { callout } => ("> ".to_string(), &callout.rich_text, "> "),
+        NotionBlock::Divider { .. } => {
+            lines.push(format!("{indent}---"));
+            return;
+        }
+        NotionBlock::Code { code } => {
+            let language = match code.language.as_str() {
+                "plain text" => "",
+                language => language,
+            };
+            let source: String = code
+                .rich_text
+                .iter()
+                .filter_map(|t| t.plain_text())
+                .collect();
+            lines.push(format!("{indent}```{language}"));
+            lines.extend(source.lines().map(|line| format!("{indent}{line}")));
+            lines.push(format!("{indent}```"));
+            return;
+        }
+        // 段組みは各列の中身を順に描画する
+        NotionBlock::ColumnList { .. } | NotionBlock::Column { .. } => {
+            render_blocks(block.children().unwrap_or_default(), indent, lines);
+            return;
+        }
+        NotionBlock::Unsupported => return,
+    };
+    render_text_block(
+        block,
+        &marker,
+        rich_text,
+        indent,
+        &format!("{indent}{child_indent}"),
+        lines,
+    );
+}
+
+fn render_text_block(
+    block: &NotionBlock,
+    marker: &str,
+    rich_text: &[NotionRichText],
+    indent: &str,
+    child_indent: &str,
+    lines: &mut Vec<String>,
+) {
+    let text = rich_text_to_markdown(rich_text);
+    let mut text_lines = text.split('\n');
+    let first_line = text_lines.next().unwrap_or_default();
+    lines.push(
+        format!("{indent}{marker}{first_line}")
+            .trim_end()
+            .to_string(),
+    );
+    lines.extend(text_lines.map(|line| format!("{child_indent}{line}").trim_end().to_string()));
+
+    if let Some(children) = block.children().filter(|children| !children.is_empty()) {
+        // リスト項目の子はそのまま続け、それ以外は空行で区切る
+        if !is_list_item(block) {
+            lines.push(child_indent.trim_end().to_string());
+        }
+        render_blocks(children, child_indent, lines);
+    }
+}
+
+fn is_list_item(block: &NotionBlock) -> bool {
+    matches!(
+        block,
+        NotionBlock::BulletedListItem { .. }
+            | NotionBlock::NumberedListItem { .. }
+            | NotionBlock::ToDo { .. }
+            | NotionBlock::Toggle { .. }
+    )
+}
+
+fn rich_text_to_markdown(rich_text: &[NotionRichText]) -> String {
+    rich_text
+        .iter()
+        .map(rich_text_segment_to_markdown)
+        .collect()
+}
+
+fn rich_text_segment_to_markdown(segment: &NotionRichText) -> String {
+    let (text, annotations, link) = match segment {
+        NotionRichText::Text {
+            text,
+            plain_text,
+            annotations,
+        } => (
+            plain_text.clone().unwrap_or_else(|| text.content.clone()),
+            annotations,
+            text.link.as_ref().map(|link| link.url.clone()),
+        ),
+        NotionRichText::Mention {
+            mention,
+            annotations,
+            plain_text,
+        } => (
+            mention_text(mention, plain_text.as_deref()),
+            annotations,
+            None,
+        ),
+        // 数式は TeX のまま $...$ で囲む
+        NotionRichText::Equation {
+            equation,
+            plain_text,
+            ..
+        } => {
+            let expression = equation["expression"]
+                .as_str()
+                .or(plain_text.as_deref())
+                .unwrap_or_default();
+            return format!("${expression}$");
+        }
+    };
+    decorate(&text, annotations, link.as_deref())
+}
+
+// メンションは Notion が返す表示文字列を優先し、無ければ種類ごとの値から組み立てる
+fn mention_text(mention: &serde_json::Value, plain_text: Option<&str>) -> String {
+    if let Some(text) = plain_text {
+        return text.to_string();
+    }
+    match mention["type"].as_str() {
+        Some("user") => format!("@{}", mention["user"]["name"].as_str().unwrap_or("user")),
+        Some("date") => mention["date"]["start"]
+            .as_str()
+            .unwrap_or_default()
+            .to_string(),
+        Some(kind) => mention[kind]["id"].as_str().unwrap_or_default().to_string(),
+        None => String::new(),
+    }
+}
+
+// 前後の空白を装飾の外に出して、Markdown として崩れないようにする
+fn decorate(text: &str, annotations: &RichTextAnnotations, link: Option<&str>) -> String {
+    let body = text.trim();
+    if body.is_empty() {
+        return text.to_string();
+    }
+    let leading = &text[..text.len() - text.trim_start().len()];
+    let trailing = &text[text.trim_end().len()..];
+
+    let mut decorated = if annotations.code {
+        format!("`{body}`")
+    } else {
+        body.to_string()
+    };
+    if annotations.strikethrough {
+        decorated = format!("~~{decorated}~~");
+    }
+    if annotations.italic {
+        decorated = format!("*{decorated}*");
+    }
+    if annotations.bold {
+        decorated = format!("**{decorated}**");
+    }
+    if let Some(url) = link {
+        decorated = format!("[{decorated}]({url})");
+    }
+    format!("{leading}{decorated}{trailing}")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::ExtractText;
+    use serde_json::Value;
+
+    fn to_json(blocks: &[NotionBlock]) -> Value {
+        serde_json::to_value(blocks).unwrap()
+    }
+
+    #[test]
+    fn test_headings_paragraphs_and_dividers() {
+        let blocks =
+            markdown_to_blocks("# 今週\n\n## 良かったこと\n#### 細目\n\n本文の\n続き\n\n---\n");
+        let json = to_json(&blocks);
+        assert_eq!(json[0]["type"], "heading_1");
+        assert_eq!(json[1]["type"], "heading_2");
+        assert_eq!(json[2]["type"], "heading_3");
+        assert_eq!(json[3]["type"], "paragraph");
+        assert_eq!(blocks[3].extract_text().as_deref(), Some("本文の 続き"));
+        assert_eq!(json[4]["type"], "divider");
+    }
+
+    #[test]
+    fn test_nested_lists_and_todos() {
+        let blocks = markdown_to_blocks(
+            "- 親\n  - 子\n    1. 孫\n- [ ] やること\n- [x] 終わったこと\n\n1. 一つ目\n2. 二つ目\n",
+        );
+        let json = to_json(&blocks);
+        assert_eq!(json[0]["type"], "bulleted_list_item");
+        let child = &json[0]["bulleted_list_item"]["children"][0];
+        assert_eq!(child["type"], "bulleted_list_item");
+        assert_eq!(
+            child["bulleted_list_item"]["children"][0]["type"],
+            "numbered_list_item"
+        );
+        assert_eq!(json[1]["type"], "to_do");
+        assert_eq!(json[1]["to_do"]["checked"], false);
+        assert_eq!(json[2]["to_do"]["checked"], true);
+        assert_eq!(blocks[2].extract_text().as_deref(), Some("終わったこと"));
+        assert_eq!(json[3]["type"], "numbered_list_item");
+        assert_eq!(json[4]["type"], "numbered_list_item");
+    }
+
+    #[test]
+    fn test_quotes_and_code_fences() {
+        let blocks =
+            markdown_to_blocks("> 引用文\n\n```rs\nfn main() {}\n```\n\n```unknown\nx\n```\n");
+        let json = to_json(&blocks);
+        assert_eq!(json[0]["type"], "quote");
+        assert_eq!(blocks[0].extract_text().as_deref(), Some("引用文"));
+        assert_eq!(json[1]["code"]["language"], "rust");
+        assert_eq!(
+            json[1]["code"]["rich_text"][0]["text"]["content"],
+            "fn main() {}"
+        );
+        assert_eq!(json[2]["code"]["language"], "plain text");
+    }
+
+    #[test]
+    fn test_inline_annotations_and_links() {
+        let blocks = markdown_to_blocks(
+            "**太字**と*斜体*と`code`と~~取り消し~~と[リンク](https://example.com)",
+        );
+        let rich_text = to_json(&blocks)[0]["paragraph"]["rich_text"].clone();
+        assert_eq!(rich_text[0]["text"]["content"], "太字");
+        assert_eq!(rich_text[0]["annotations"]["bold"], true);
+        assert_eq!(rich_text[1]["annotations"]["bold"], false);
+        assert_eq!(rich_text[2]["annotations"]["italic"], true);
+        assert_eq!(rich_text[4]["annotations"]["code"], true);
+        assert_eq!(rich_text[6]["annotations"]["strikethrough"], true);
+        assert_eq!(rich_text[8]["text"]["content"], "リンク");
+        assert_eq!(rich_text[8]["text"]["link"]["url"], "https://example.com");
+    }
+
+    #[test]
+    fn test_blocks_to_markdown_preserves_structure() {
+        let markdown = "# 今週\n\n本文\n\n- 親\n  1. 子\n  2. 次の子\n- [x] 終わったこと\n- [ ] やること\n\n> 引用\n\n```rust\nfn main() {}\n```\n\n---";
+        assert_eq!(blocks_to_markdown(&markdown_to_blocks(markdown)), markdown);
+    }
+
+    #[test]
+    fn test_blocks_to_markdown_renders_rich_text() {
+        let blocks: Vec<NotionBlock> = serde_json::from_value(serde_json::json!([
+            {
+                "type": "paragraph",
+                "paragraph": {
+                    "rich_text": [
+                        {"type": "text", "text": {"content": "太字 "}, "annotations": {"bold": true}},
+                        {"type": "text", "text": {"content": "リンク", "link": {"url": "https://example.com"}}, "plain_text": "リンク"},
+                        {"type": "mention", "mention": {"type": "user", "user": {"id": "u1"}}, "plain_text": "@太郎"},
+                        {"type": "mention", "mention": {"type": "date", "date": {"start": "2024-01-01"}}},
+                        {"type": "equation", "equation": {"expression": "e=mc^2"}, "plain_text": "e=mc^2"}
+                    ]
+                }
+            },
+            {
+                "type": "quote",
+                "quote": {
+                    "rich_text": [{"type": "text", "text": {"content": "一行目\n二行目"}}],
+                    "children": [{"type": "paragraph", "paragraph": {"rich_text": [{"type": "text", "text": {"content": "補足"}}]}}]
+                }
+            },
+            {"type": "image", "image": {}}
+        ]))
+        .unwrap();
+
+        assert_eq!(
+            blocks_to_markdown(&blocks),
+            "**太字** [リンク](https://example.com)@太郎2024-01-01$e=mc^2$\n\n> 一行目\n> 二行目\n>\n> 補足"
+        );
+    }
+}
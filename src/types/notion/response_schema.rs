@@ -0,0 +1,192 @@
+use serde_json::{json, Value};
+
+use super::{markdown::NOTION_CODE_LANGUAGES, NotionBlock};
+
+// 生成を許可するブロックと、その中身の形
+const GENERATED_BLOCK_TYPES: &[(&str, ContentShape)] = &[
+    ("heading_1", ContentShape::RichText),
+    ("heading_2", ContentShape::RichText),
+    ("heading_3", ContentShape::RichText),
+    ("paragraph", ContentShape::RichText),
+    ("bulleted_list_item", ContentShape::RichTextWithChildren),
+    ("numbered_list_item", ContentShape::RichTextWithChildren),
+    ("to_do", ContentShape::ToDo),
+    ("toggle", ContentShape::RichTextWithChildren),
+    ("quote", ContentShape::RichText),
+    ("callout", ContentShape::RichText),
+    ("divider", ContentShape::Empty),
+    ("code", ContentShape::Code),
+];
+
+// Notion が受け付け、RichTextColor としても読める色だけを許可する
+const GENERATED_COLORS: &[&str] = &[
+    "default", "gray", "brown", "orange", "yellow", "green", "blue", "purple", "pink", "red",
+];
+
+#[derive(Debug, Clone, Copy)]
+enum ContentShape {
+    RichText,
+    RichTextWithChildren,
+    ToDo,
+    Code,
+    Empty,
+}
+
+impl NotionBlock {
+    /// Gemini の responseSchema に渡す、ブロック配列のスキーマ。
+    /// responseSchema は再帰を表現できないため、子ブロックは 1 階層までとする
+    pub fn gemini_response_schema() -> Value {
+        json!({
+            "type": "ARRAY",
+            "items": block_schema(true),
+        })
+    }
+}
+
+fn block_schema(allow_children: bool) -> Value {
+    let variants: Vec<Value> = GENERATED_BLOCK_TYPES
+        .iter()
+        .map(|(block_type, shape)| {
+            json!({
+                "type": "OBJECT",
+                "properties": {
+                    "type": { "type": "STRING", "enum": [block_type] },
+                    *block_type: content_schema(*shape, allow_children),
+                },
+                "required": ["type", block_type],
+                "propertyOrdering": ["type", block_type],
+            })
+        })
+        .collect();
+    json!({ "anyOf": variants })
+}
+
+fn content_schema(shape: ContentShape, allow_children: bool) -> Value {
+    let mut properties = serde_json::Map::new();
+    let mut required = vec!["rich_text"];
+    match shape {
+        ContentShape::Empty => return json!({ "type": "OBJECT", "properties": {} }),
+        ContentShape::RichText | ContentShape::RichTextWithChildren => {}
+        ContentShape::ToDo => {
+            properties.insert("checked".to_string(), json!({ "type": "BOOLEAN" }));
+            required.push("checked");
+        }
+        ContentShape::Code => {
+            properties.insert(
+                "language".to_string(),
+                json!({ "type": "STRING", "enum": NOTION_CODE_LANGUAGES }),
+            );
+            required.push("language");
+        }
+    }
+    properties.insert(
+        "rich_text".to_string(),
+        json!({ "type": "ARRAY", "items": rich_text_schema() }),
+    );
+    if allow_children
+        && matches!(
+            shape,
+            ContentShape::RichTextWithChildren | ContentShape::ToDo
+        )
+    {
+        properties.insert(
+            "children".to_string(),
+            json!({ "type": "ARRAY", "items": block_schema(false) }),
+        );
+    }
+
+    json!({
+        "type": "OBJECT",
+        "properties": properties,
+        "required": required,
+    })
+}
+
+fn rich_text_schema() -> Value {
+    let flag = json!({ "type": "BOOLEAN" });
+    json!({
+        "type": "OBJECT",
+        "properties": {
+            "type": { "type": "STRING", "enum": ["text"] },
+            "text": {
+                "type": "OBJECT",
+                "properties": {
+                    "content": { "type": "STRING" },
+                    "link": {
+                        "type": "OBJECT",
+                        "nullable": true,
+                        "properties": { "url": { "type": "STRING" } },
+                        "required": ["url"],
+                    },
+                },
+                "required": ["content"],
+            },
+            "annotations": {
+                "type": "OBJECT",
+                "properties": {
+                    "bold": flag,
+                    "italic": flag,
+                    "strikethrough": flag,
+                    "underline": flag,
+                    "code": flag,
+                    "color": { "type": "STRING", "enum": GENERATED_COLORS },
+                },
+            },
+        },
+        "required": ["type", "text"],
+        "propertyOrdering": ["type", "text", "annotations"],
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::RichTextColor;
+
+    // スキーマに載せたブロックはすべて NotionBlock として読めなければならない
+    #[test]
+    fn test_generated_block_types_deserialize() {
+        for (block_type, shape) in GENERATED_BLOCK_TYPES {
+            let content = match shape {
+                ContentShape::Empty => json!({}),
+                ContentShape::ToDo => json!({ "rich_text": [], "checked": false }),
+                ContentShape::Code => json!({ "rich_text": [], "language": "rust" }),
+                _ => json!({ "rich_text": [] }),
+            };
+            let block: NotionBlock =
+                serde_json::from_value(json!({ "type": block_type, *block_type: content }))
+                    .unwrap();
+            assert!(
+                !matches!(block, NotionBlock::Unsupported),
+                "{block_type} is not supported"
+            );
+        }
+    }
+
+    #[test]
+    fn test_generated_colors_deserialize() {
+        for color in GENERATED_COLORS {
+            serde_json::from_value::<RichTextColor>(json!(color)).unwrap();
+        }
+    }
+
+    #[test]
+    fn test_children_are_limited_to_one_level() {
+        let schema = NotionBlock::gemini_response_schema();
+        let list_item = &schema["items"]["anyOf"][4]["properties"]["bulleted_list_item"];
+        let child_list_item = &list_item["properties"]["children"]["items"]["anyOf"][4]
+            ["properties"]["bulleted_list_item"];
+        assert!(list_item["properties"]["children"].is_object());
+        assert!(child_list_item["properties"]["rich_text"].is_object());
+        assert!(child_list_item["properties"]["children"].is_null());
+    }
+
+    #[test]
+    fn test_code_language_is_limited_to_notion_languages() {
+        let schema = NotionBlock::gemini_response_schema();
+        let language =
+            &schema["items"]["anyOf"][11]["properties"]["code"]["properties"]["language"];
+        assert_eq!(language["enum"], json!(NOTION_CODE_LANGUAGES));
+        assert!(NOTION_CODE_LANGUAGES.contains(&"plain text"));
+    }
+}